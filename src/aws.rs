@@ -1,19 +1,38 @@
-use crate::configs::Configs;
-use crate::creds::Credentials;
+use crate::configs::{
+    AssumeRoleConfigs, AuthConfigs, AuthOutput, Configs, Hook, SessionTokenConfigs, SsoConfigs,
+};
+use crate::creds::{
+    Credentials, Profile, ProfileCredentials, ACCESS_KEY_ID_KEY, SECRET_ACCESS_KEY_KEY,
+};
 use crate::ctx;
+use crate::lock::{self, Acquired};
+use crate::logs::ScriptLog;
+use crate::process;
+use crate::prompt::{Prompter, TerminalPrompter};
+use crate::sso::{self, Sso};
+use crate::state::State;
+use crate::sts::{self, Sts};
 
 use dirs::home_dir;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
 
 use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
 use handlebars::Handlebars;
 use once_cell::sync::Lazy;
+use regex::Regex;
 use serde_json::json;
-use skim::prelude::{unbounded, Key};
-use skim::{Skim, SkimItemReceiver, SkimItemSender, SkimOptions};
+use tempfile::TempPath;
 
 pub static CREDENTIALS_PATH: Lazy<PathBuf> = Lazy::new(|| {
     let mut path = home_dir().unwrap();
@@ -26,63 +45,1172 @@ pub struct AWS<'a, P: AsRef<Path>> {
     configs: Rc<Configs>,
     credentials_path: P,
     reg: Handlebars<'a>,
+    prompter: Box<dyn Prompter>,
+    state_path: Option<PathBuf>,
+    lock_dir: Option<PathBuf>,
+    log_dir: Option<PathBuf>,
+    sso_cache_dir: Option<PathBuf>,
+    non_interactive: bool,
+    /// Prefix of output of scripts, which is set while scripts run in parallel
+    output_prefix: Option<String>,
 }
 
 impl<P: AsRef<Path>> AWS<'_, P> {
     pub fn new(configs: Rc<Configs>, credentials_path: P) -> Result<Self> {
+        let mut reg = Handlebars::new();
+        // scripts are not HTML, so render values as they are
+        reg.register_escape_fn(handlebars::no_escape);
+        reg.set_strict_mode(configs.auth.strict);
         Ok(Self {
             configs,
             credentials_path,
-            reg: Handlebars::new(),
+            reg,
+            prompter: Box::new(TerminalPrompter::default()),
+            state_path: None,
+            lock_dir: None,
+            log_dir: None,
+            sso_cache_dir: None,
+            non_interactive: false,
+            output_prefix: None,
         })
     }
-}
 
-impl<P: AsRef<Path>> ctx::CTX for AWS<'_, P> {
-    fn auth(&self, profile: &str) -> Result<ctx::Context, ctx::CTXError> {
-        let script_template = self
+    /// Record usage of profiles to the state file, which is disabled by default
+    pub fn with_state_path(mut self, state_path: PathBuf) -> Self {
+        self.state_path = Some(state_path);
+        self
+    }
+
+    /// Lock auth of each profile by a file in the directory, so that concurrent invocations
+    /// for the same profile run the auth script only once, which is disabled by default
+    pub fn with_lock_dir(mut self, lock_dir: PathBuf) -> Self {
+        self.lock_dir = Some(lock_dir);
+        self
+    }
+
+    /// Write output of auth scripts to a log file in the directory for each run,
+    /// which is disabled by default
+    pub fn with_log_dir(mut self, log_dir: PathBuf) -> Self {
+        self.log_dir = Some(log_dir);
+        self
+    }
+
+    /// Cache access tokens of IAM Identity Center in the directory for `sso` auth,
+    /// which is disabled by default so that a login is asked every time
+    pub fn with_sso_cache_dir(mut self, sso_cache_dir: PathBuf) -> Self {
+        self.sso_cache_dir = Some(sso_cache_dir);
+        self
+    }
+
+    /// Run scripts without stdin, so that scripts asking for input fail instead of hanging
+    pub fn with_non_interactive(mut self, non_interactive: bool) -> Self {
+        self.non_interactive = non_interactive;
+        self
+    }
+
+    fn close_stdin(&self, command: &mut Command) {
+        if self.non_interactive {
+            command.stdin(Stdio::null());
+        }
+    }
+
+    fn create_log(&self, profile: &str) -> Option<ScriptLog> {
+        ScriptLog::create(self.log_dir.as_ref()?, profile)
+            .map_err(|e| {
+                debug!(
+                    "failed to create a log of profile ({}), ignored: {:?}",
+                    profile, e
+                )
+            })
+            .ok()
+    }
+
+    /// Lock auth of the profile, returns `None` if locks are disabled or unavailable
+    fn lock_auth(&self, profile: &str) -> Option<Acquired> {
+        let timeout = self.configs.get_lock_timeout();
+        match lock::acquire(self.lock_dir.as_ref()?, profile, timeout) {
+            Ok(Acquired::Stale) => {
+                warn!(
+                    "auth of profile ({}) in another process is not finished in {} seconds, run auth anyway",
+                    profile,
+                    timeout.as_secs()
+                );
+                Some(Acquired::Stale)
+            }
+            Ok(acquired) => Some(acquired),
+            Err(e) => {
+                debug!(
+                    "failed to lock auth of profile ({}), ignored: {:?}",
+                    profile, e
+                );
+                None
+            }
+        }
+    }
+
+    fn load_state(&self) -> State {
+        self.state_path
+            .as_ref()
+            .map(|p| {
+                State::load_state(p).unwrap_or_else(|e| {
+                    debug!("failed to load state, ignored: {:?}", e);
+                    State::default()
+                })
+            })
+            .unwrap_or_default()
+    }
+
+    fn record_usage(&self, name: &str) {
+        if let Some(state_path) = &self.state_path {
+            let mut state = self.load_state();
+            state.record_usage(name, Utc::now());
+            if let Err(e) = state.dump_state(state_path) {
+                debug!(
+                    "failed to record usage of profile ({}), ignored: {:?}",
+                    name, e
+                );
+            }
+        }
+    }
+
+    pub fn with_prompter(mut self, prompter: Box<dyn Prompter>) -> Self {
+        self.prompter = prompter;
+        self
+    }
+
+    fn is_protected(&self, name: &str) -> bool {
+        self.configs
+            .get_profile_configs(name)
+            .map(|c| c.protected)
+            .unwrap_or_default()
+    }
+
+    fn to_context(&self, profile: &Profile) -> ctx::Context {
+        ctx::Context {
+            name: profile.name.to_string(),
+            active: profile.default,
+            protected: self.is_protected(&profile.name),
+        }
+    }
+
+    fn to_context_details(&self, profile: &Profile, state: &State) -> ctx::ContextDetails {
+        let configs = self
             .configs
-            .auth_commands
-            .get(profile)
-            // fallback to default configuration if a commend for the profile is not found
-            .or_else(|| {
-                self.configs
-                    .auth_commands
-                    .get(Configs::DEFAULT_AUTH_COMMAND_KEY)
+            .get_profile_configs(&profile.name)
+            .cloned()
+            .unwrap_or_default();
+        ctx::ContextDetails {
+            name: profile.name.to_string(),
+            active: profile.default,
+            aliases: configs.aliases,
+            groups: self
+                .configs
+                .groups_of(&profile.name)
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            region: configs.region,
+            description: configs.description,
+            tags: configs.tags,
+            protected: configs.protected,
+            access_key_id: profile.get(ACCESS_KEY_ID_KEY).map(String::from),
+            secret_access_key: profile.get(SECRET_ACCESS_KEY_KEY).map(String::from),
+            key_type: profile.key_type(),
+            expiration: profile.expiration(),
+            auth_command: self
+                .configs
+                .get_auth_configs(&profile.name)
+                .map(|a| a.command()),
+            last_used: state
+                .get_profile_state(&profile.name)
+                .and_then(|s| s.last_used),
+        }
+    }
+
+    /// Ask a user to confirm an operation on a protected profile
+    fn confirm_protected(&self, name: &str) -> Result<(), ctx::CTXError> {
+        if !self.is_protected(name) {
+            return Ok(());
+        }
+        let mut answers = vec![name.to_string()];
+        let account_id = self
+            .configs
+            .get_profile_configs(name)
+            .and_then(|c| c.account_id.clone());
+        let message = match &account_id {
+            Some(_) => format!(
+                "profile ({}) is protected, type the profile name or the account id to continue",
+                name
+            ),
+            None => format!(
+                "profile ({}) is protected, type the profile name to continue",
+                name
+            ),
+        };
+        answers.extend(account_id);
+        if self.prompter.confirm(&message, &answers)? {
+            Ok(())
+        } else {
+            Err(ctx::CTXError::NotConfirmed {
+                profile: name.to_string(),
+                source: None,
             })
-            .ok_or_else(|| ctx::CTXError::NoAuthConfiguration {
+        }
+    }
+
+    /// Confirm activation of a protected profile unless it is already active
+    fn confirm_activation(&self, name: &str) -> Result<(), ctx::CTXError> {
+        let active = Credentials::load_credentials(&self.credentials_path)
+            .and_then(|creds| creds.get_default_profile())
+            .map(|p| p.name == name)
+            .unwrap_or_default();
+        if active {
+            return Ok(());
+        }
+        self.confirm_protected(name)
+    }
+
+    /// Run scripts of the hook with the old (active) profile and the new profile.
+    /// Failures of `post_*` hooks are only warned since the operation is already done
+    fn run_hooks(&self, hook: Hook, old: Option<&str>, new: &str) -> Result<(), ctx::CTXError> {
+        let hooks = self.configs.get_hooks(new, hook);
+        if hooks.is_empty() {
+            return Ok(());
+        }
+        let mut envs = self
+            .configs
+            .get_profile_configs(new)
+            .map(|p| p.env.clone())
+            .unwrap_or_default();
+        envs.insert(
+            "AWSCTX_OLD_PROFILE".to_string(),
+            old.unwrap_or_default().to_string(),
+        );
+        envs.insert("AWSCTX_NEW_PROFILE".to_string(), new.to_string());
+        let mut data = self.template_data(new, &envs, &[]);
+        data["old_profile"] = json!(old.unwrap_or_default());
+        data["new_profile"] = json!(new);
+
+        let result = hooks.into_iter().try_for_each(|script| {
+            debug!("run {} hook for profile ({})", hook, new);
+            let script = self.render(new, script, &data)?;
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(script).envs(&envs);
+            self.close_stdin(&mut command);
+            let exit = match &self.output_prefix {
+                Some(prefix) => process::run_with_output(
+                    &mut command,
+                    None,
+                    &process::OutputOptions {
+                        prefix: Some(prefix),
+                        ..Default::default()
+                    },
+                )
+                .map(|o| o.exit),
+                None => process::run(&mut command, None),
+            };
+            let source = match exit {
+                Ok(exit) if exit.success() => return Ok(()),
+                Ok(process::Exit::Exited(status)) => {
+                    anyhow!("hook exited with {}", process::describe_status(&status))
+                }
+                Ok(process::Exit::TimedOut) => anyhow!("hook timed out"),
+                Err(e) => anyhow!("failed to execute a hook: {}", e),
+            };
+            Err(ctx::CTXError::HookFailed {
+                hook: hook.to_string(),
+                profile: new.to_string(),
+                source: Some(source),
+            })
+        });
+        match (hook, result) {
+            (Hook::PostUse | Hook::PostAuth, Err(e)) => {
+                warn!("{} hook for profile ({}) failed", hook, new);
+                debug!("caused error: {:?}", e);
+                Ok(())
+            }
+            (_, result) => result,
+        }
+    }
+
+    /// Make the profile active with `pre_use` and `post_use` hooks
+    fn switch(&self, name: &str) -> Result<ctx::Context, ctx::CTXError> {
+        let creds = Credentials::load_credentials(&self.credentials_path)?;
+        // never run hooks for an unknown profile
+        creds.get_profile(name)?;
+        let old = creds.get_default_profile().ok().map(|p| p.name);
+        self.run_hooks(Hook::PreUse, old.as_deref(), name)?;
+        let context = self.activate(name)?;
+        self.run_hooks(Hook::PostUse, old.as_deref(), name)?;
+        Ok(context)
+    }
+
+    fn activate(&self, name: &str) -> Result<ctx::Context, ctx::CTXError> {
+        let profile = Credentials::update_credentials(&self.credentials_path, |creds| {
+            creds.set_default_profile(name)
+        })?;
+        self.record_usage(name);
+        Ok(self.to_context(&profile))
+    }
+
+    /// Data to render the auth script, reserved names such as `profile` take precedence over `vars`
+    fn template_data(
+        &self,
+        profile: &str,
+        envs: &HashMap<String, String>,
+        args: &[String],
+    ) -> serde_json::Value {
+        let mut data = serde_json::Map::new();
+        if let Some(p) = self.configs.get_profile_configs(profile) {
+            if let Some(region) = &p.region {
+                data.insert("region".to_string(), json!(region));
+            }
+            if let Some(account_id) = &p.account_id {
+                data.insert("account_id".to_string(), json!(account_id));
+            }
+        }
+        for (k, v) in self.configs.get_vars(profile) {
+            data.insert(k, json!(v));
+        }
+        let mut env = std::env::vars().collect::<HashMap<String, String>>();
+        env.extend(envs.clone());
+        data.insert("profile".to_string(), json!(profile));
+        data.insert("env".to_string(), json!(env));
+        if let Some(user) = os_user() {
+            data.insert("user".to_string(), json!(user));
+        }
+        data.insert(
+            "args".to_string(),
+            json!(args
+                .iter()
+                .map(|a| shell_quote(a))
+                .collect::<Vec<_>>()
+                .join(" ")),
+        );
+        serde_json::Value::Object(data)
+    }
+
+    fn render(
+        &self,
+        profile: &str,
+        template: &str,
+        data: &serde_json::Value,
+    ) -> Result<String, ctx::CTXError> {
+        self.reg
+            .render_template(template, data)
+            .map_err(|e| ctx::CTXError::InvalidConfigurations {
+                message: format!(
+                    "failed to render script of profile ({}), {}",
+                    profile, e.desc
+                ),
+                source: Some(anyhow!("failed to render script {}", e)),
+            })
+    }
+
+    /// Build a command of the auth, which is one of
+    /// - `argv` executed directly, each element of which is rendered separately
+    /// - the script run by the interpreter, or executed directly if it starts with a shebang
+    /// - the script run by `sh -c`
+    ///
+    /// `{{args}}` in `data` is quoted for `sh`, so it is replaced by the arguments joined by spaces
+    /// for commands not run by `sh -c`.
+    fn auth_command(
+        &self,
+        profile: &str,
+        auth: &AuthConfigs,
+        data: &serde_json::Value,
+        args: &[String],
+    ) -> Result<(Command, Option<TempPath>), ctx::CTXError> {
+        let mut unquoted = data.clone();
+        unquoted["args"] = json!(args.join(" "));
+        if let Some((program, rest)) = auth.argv().split_first() {
+            let data = &unquoted;
+            let mut command = Command::new(self.render(profile, program, data)?);
+            for arg in rest {
+                // `{{args}}` as an element is expanded to the arguments as they are
+                if arg.trim() == "{{args}}" {
+                    command.args(args);
+                } else {
+                    command.arg(self.render(profile, arg, data)?);
+                }
+            }
+            return Ok((command, None));
+        }
+
+        let template = auth.script().map_or("", |s| s.as_str());
+        if auth.interpreter().is_none() && !template.starts_with("#!") {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(self.render(profile, template, data)?);
+            return Ok((command, None));
+        }
+        let script = self.render(profile, template, &unquoted)?;
+        let path = write_script(&script).map_err(|e| ctx::CTXError::UnexpectedError {
+            source: Some(anyhow!(
+                "failed to write an auth script of profile ({}): {}",
+                profile,
+                e
+            )),
+        })?;
+        let command = match auth.interpreter().map(|i| i.split_whitespace()) {
+            Some(mut words) => {
+                // an empty interpreter is rejected on loading configurations
+                let mut command = Command::new(words.next().unwrap_or("sh"));
+                command.args(words).arg(&path);
+                command
+            }
+            None => Command::new(&path),
+        };
+        Ok((command, Some(path)))
+    }
+
+    /// Authenticate the profile by assuming the role if `assume_role` is configured,
+    /// or else by running the auth script
+    fn authenticate(&self, profile: &str, args: &[String]) -> Result<(), ctx::CTXError> {
+        let auth = self.configs.get_auth_configs(profile).ok_or_else(|| {
+            ctx::CTXError::NoAuthConfiguration {
                 profile: profile.to_string(),
                 source: None,
+            }
+        })?;
+        let mut lock = match self.lock_auth(profile) {
+            Some(Acquired::Locked(lock)) => Some(lock),
+            Some(Acquired::Finished) => {
+                info!(
+                    "profile ({}) is authenticated by another process, reuse it",
+                    profile
+                );
+                return Ok(());
+            }
+            _ => None,
+        };
+        if let Some(assume_role) = auth.assume_role() {
+            self.assume_role(profile, assume_role, args, &mut vec![])?;
+        } else if let Some(session_token) = auth.session_token() {
+            self.get_session_token(profile, session_token)?;
+        } else if let Some(sso) = auth.sso() {
+            self.get_role_credentials(profile, sso)?;
+        } else {
+            self.run_auth_script(profile, auth, args)?;
+        }
+        if let Some(lock) = lock.as_mut() {
+            if let Err(e) = lock.finish() {
+                debug!(
+                    "failed to record auth of profile ({}), ignored: {:?}",
+                    profile, e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Client of STS for the profile
+    fn sts(&self, profile: &str) -> Sts {
+        let region = self
+            .configs
+            .get_profile_configs(profile)
+            .and_then(|p| p.region.as_deref());
+        Sts::new(self.configs.auth.endpoints.sts.as_deref(), region)
+            .with_timeout(self.configs.get_auth_timeout(profile))
+    }
+
+    /// Ask the code of the MFA device
+    fn ask_mfa_code(&self, profile: &str, serial: &str) -> Result<String, ctx::CTXError> {
+        self.prompter.input(&format!(
+            "MFA code of device ({}) for profile ({})",
+            serial, profile
+        ))
+    }
+
+    /// Write credentials obtained by auth to the profile
+    fn write_credentials(
+        &self,
+        profile: &str,
+        credentials: &ProfileCredentials,
+    ) -> Result<Profile, ctx::CTXError> {
+        Credentials::update_credentials(&self.credentials_path, |creds| {
+            Ok(creds.set_credentials(profile, credentials))
+        })
+    }
+
+    /// Credentials of the profile in the credentials file, `None` if not found
+    fn load_profile_credentials(
+        &self,
+        profile: &str,
+    ) -> Result<Option<ProfileCredentials>, ctx::CTXError> {
+        match Credentials::load_credentials(&self.credentials_path) {
+            Ok(creds) => Ok(creds
+                .get_profile(profile)
+                .ok()
+                .and_then(|p| p.credentials())),
+            // credentials may not exist yet before the first auth
+            Err(ctx::CTXError::CannotReadCredentials { source: _ }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get MFA session credentials with long-term keys of the source profile, then write them to the profile
+    fn get_session_token(
+        &self,
+        profile: &str,
+        session_token: &SessionTokenConfigs,
+    ) -> Result<(), ctx::CTXError> {
+        let source = &session_token.source_profile;
+        let credentials = match self.load_profile_credentials(source)? {
+            Some(c) if c.session_token.is_none() => c,
+            Some(_) => {
+                return Err(ctx::CTXError::AuthRequestFailed {
+                    profile: profile.to_string(),
+                    message: format!(
+                        "source profile ({}) has temporary credentials, long-term keys of an IAM user are required",
+                        source
+                    ),
+                    source: None,
+                })
+            }
+            None => {
+                return Err(ctx::CTXError::AuthRequestFailed {
+                    profile: profile.to_string(),
+                    message: format!("credentials of source profile ({}) are not found", source),
+                    source: None,
+                })
+            }
+        };
+        let mfa_code = self.ask_mfa_code(profile, &session_token.mfa_serial)?;
+        let sts = self.sts(profile);
+        debug!(
+            "get session token of profile ({}) with profile ({}) by {}",
+            profile,
+            source,
+            sts.endpoint()
+        );
+        let credentials = sts
+            .get_session_token(
+                &credentials,
+                &sts::GetSessionToken {
+                    duration: session_token.duration,
+                    mfa: Some((&session_token.mfa_serial, &mfa_code)),
+                },
+            )
+            .map_err(|e| ctx::CTXError::AuthRequestFailed {
+                profile: profile.to_string(),
+                message: e.to_string(),
+                source: Some(e),
             })?;
-        let script = self
-            .reg
-            .render_template(script_template, &json!({ "profile": profile }))
-            .map_err(|e| ctx::CTXError::InvalidConfigurations {
-                message: format!("failed to render script of profile {}", profile),
-                source: Some(anyhow!("failed to render script {}", e)),
+        self.write_credentials(profile, &credentials)?;
+        Ok(())
+    }
+
+    /// Assume the role of the profile, then write the credentials to the profile.
+    /// `chain` is profiles whose roles are being assumed, to detect a cycle of source profiles
+    fn assume_role(
+        &self,
+        profile: &str,
+        assume_role: &AssumeRoleConfigs,
+        args: &[String],
+        chain: &mut Vec<String>,
+    ) -> Result<ProfileCredentials, ctx::CTXError> {
+        chain.push(profile.to_string());
+        let source = self.source_credentials(profile, &assume_role.source_profile, chain)?;
+        let envs = self
+            .configs
+            .get_profile_configs(profile)
+            .map(|p| p.env.clone())
+            .unwrap_or_default();
+        let data = self.template_data(profile, &envs, args);
+        let session_name = self.render(
+            profile,
+            assume_role
+                .session_name
+                .as_deref()
+                .unwrap_or(AssumeRoleConfigs::DEFAULT_SESSION_NAME),
+            &data,
+        )?;
+        let mfa_code = assume_role
+            .mfa_serial
+            .as_deref()
+            .map(|serial| self.ask_mfa_code(profile, serial))
+            .transpose()?;
+        let sts = self.sts(profile);
+        debug!(
+            "assume role ({}) of profile ({}) with profile ({}) by {}",
+            assume_role.role_arn,
+            profile,
+            assume_role.source_profile,
+            sts.endpoint()
+        );
+        let credentials = sts
+            .assume_role(
+                &source,
+                &sts::AssumeRole {
+                    role_arn: &assume_role.role_arn,
+                    session_name: &session_name,
+                    duration: assume_role.duration,
+                    external_id: assume_role.external_id.as_deref(),
+                    mfa: assume_role.mfa_serial.as_deref().zip(mfa_code.as_deref()),
+                },
+            )
+            .map_err(|e| ctx::CTXError::AuthRequestFailed {
+                profile: profile.to_string(),
+                message: e.to_string(),
+                source: Some(e),
             })?;
+        self.write_credentials(profile, &credentials)?;
+        Ok(credentials)
+    }
 
-        let status = Command::new("sh")
-            .arg("-c")
-            .arg(script)
-            .status()
-            .map_err(|e| ctx::CTXError::InvalidConfigurations {
+    /// Credentials of the source profile to assume the role of the profile.
+    /// They are reused unless expired, otherwise the role of the source profile is assumed first if configured
+    fn source_credentials(
+        &self,
+        profile: &str,
+        source: &str,
+        chain: &mut Vec<String>,
+    ) -> Result<ProfileCredentials, ctx::CTXError> {
+        if chain.iter().any(|p| p == source) {
+            return Err(ctx::CTXError::InvalidConfigurations {
                 message: format!(
-                    "failed to execute an auth script of profile ({}), check configurations",
-                    profile
+                    "source profiles of profile ({}) are in a cycle ({} -> {})",
+                    chain[0],
+                    chain.join(" -> "),
+                    source
                 ),
-                source: Some(anyhow!("failed to execute an auth script: {}", e)),
-            })?;
-        if !status.success() {
-            return Err(ctx::CTXError::InvalidConfigurations {
+                source: None,
+            });
+        }
+        match self.load_profile_credentials(source)? {
+            Some(c) if c.expiration.is_none_or(|e| e > Utc::now()) => Ok(c),
+            _ => match self
+                .configs
+                .get_auth_configs(source)
+                .and_then(|a| a.assume_role())
+            {
+                Some(assume_role) => {
+                    info!("assume role of source profile ({}) first", source);
+                    self.assume_role(source, assume_role, &[], chain)
+                }
+                None => Err(ctx::CTXError::AuthRequestFailed {
+                    profile: profile.to_string(),
+                    message: format!(
+                        "credentials of source profile ({}) are not found or expired, authenticate it first",
+                        source
+                    ),
+                    source: None,
+                }),
+            },
+        }
+    }
+
+    /// Get role credentials with the access token of IAM Identity Center, then write them to the profile.
+    /// The cached token is reused, and a login is asked if it is expired or rejected
+    fn get_role_credentials(&self, profile: &str, sso: &SsoConfigs) -> Result<(), ctx::CTXError> {
+        let endpoints = &self.configs.auth.endpoints;
+        let client = Sso::new(
+            endpoints.sso_oidc.as_deref(),
+            endpoints.sso_portal.as_deref(),
+            &sso.region,
+        )
+        .with_timeout(self.configs.get_auth_timeout(profile));
+        let request_failed = |e: anyhow::Error| ctx::CTXError::AuthRequestFailed {
+            profile: profile.to_string(),
+            message: e.to_string(),
+            source: Some(e),
+        };
+        let cached = self
+            .sso_cache_dir
+            .as_ref()
+            .and_then(|dir| sso::load_token(dir, &sso.start_url))
+            .filter(|t| t.is_valid(Utc::now()));
+        let mut rejected = None;
+        if let Some(token) = cached {
+            debug!(
+                "use the cached token of {} for profile ({})",
+                sso.start_url, profile
+            );
+            let credentials = client
+                .get_role_credentials(&token.access_token, &sso.account_id, &sso.role_name)
+                .map_err(request_failed)?;
+            if let Some(credentials) = credentials {
+                self.write_credentials(profile, &credentials)?;
+                return Ok(());
+            }
+            info!(
+                "the cached token of {} is rejected, login again",
+                sso.start_url
+            );
+            rejected = Some(token.access_token);
+        }
+        let token = self.login_sso(profile, &client, sso, rejected.as_deref())?;
+        match client
+            .get_role_credentials(&token.access_token, &sso.account_id, &sso.role_name)
+            .map_err(request_failed)?
+        {
+            Some(credentials) => {
+                self.write_credentials(profile, &credentials)?;
+                Ok(())
+            }
+            None => Err(ctx::CTXError::AuthRequestFailed {
+                profile: profile.to_string(),
+                message: format!("the access token of {} is rejected", sso.start_url),
+                source: None,
+            }),
+        }
+    }
+
+    /// Login to IAM Identity Center by the device authorization flow, then cache the token.
+    /// Logins for the same start URL are locked, and a token cached by another process meanwhile is reused
+    /// unless `rejected` is the token which has just been rejected
+    fn login_sso(
+        &self,
+        profile: &str,
+        client: &Sso,
+        sso: &SsoConfigs,
+        rejected: Option<&str>,
+    ) -> Result<sso::Token, ctx::CTXError> {
+        let key = format!("sso {}", sso.start_url);
+        let mut lock = match self.lock_auth(&key) {
+            Some(Acquired::Locked(lock)) => Some(lock),
+            _ => None,
+        };
+        let cached = self
+            .sso_cache_dir
+            .as_ref()
+            .and_then(|dir| sso::load_token(dir, &sso.start_url))
+            .filter(|t| t.is_valid(Utc::now()) && Some(t.access_token.as_str()) != rejected);
+        if let Some(token) = cached {
+            info!(
+                "{} is logged in by another process, reuse it",
+                sso.start_url
+            );
+            return Ok(token);
+        }
+        if self.non_interactive {
+            return Err(ctx::CTXError::InputRequired {
+                message: format!("login to IAM Identity Center ({})", sso.start_url),
+                source: None,
+            });
+        }
+        let token = self.run_device_authorization(client, sso).map_err(|e| {
+            ctx::CTXError::AuthRequestFailed {
+                profile: profile.to_string(),
+                message: e.to_string(),
+                source: Some(e),
+            }
+        })?;
+        if let Some(dir) = self.sso_cache_dir.as_ref() {
+            if let Err(e) = sso::save_token(dir, &token) {
+                debug!(
+                    "failed to cache the token of {}, ignored: {:?}",
+                    sso.start_url, e
+                );
+            }
+        }
+        if let Some(lock) = lock.as_mut() {
+            if let Err(e) = lock.finish() {
+                debug!(
+                    "failed to record login to {}, ignored: {:?}",
+                    sso.start_url, e
+                );
+            }
+        }
+        Ok(token)
+    }
+
+    fn run_device_authorization(&self, client: &Sso, sso: &SsoConfigs) -> Result<sso::Token> {
+        let now = Utc::now();
+        // the client registered on the last login is reused while it is valid as AWS CLI does
+        let registration = match self
+            .sso_cache_dir
+            .as_ref()
+            .and_then(|dir| sso::load_token(dir, &sso.start_url))
+            .and_then(|t| t.registration(now))
+        {
+            Some(registration) => registration,
+            None => client.register_client()?,
+        };
+        let device = client.start_device_authorization(&registration, &sso.start_url)?;
+        info!(
+            "open {} in a browser and confirm the code ({}) to login to {}",
+            device
+                .verification_uri_complete
+                .as_deref()
+                .unwrap_or(&device.verification_uri),
+            device.user_code,
+            sso.start_url
+        );
+        let (access_token, expires_at) = client.create_token(&registration, &device)?;
+        Ok(sso::Token {
+            start_url: sso.start_url.clone(),
+            region: sso.region.clone(),
+            access_token,
+            expires_at,
+            client_id: Some(registration.client_id),
+            client_secret: Some(registration.client_secret),
+            registration_expires_at: Some(registration.expires_at),
+        })
+    }
+
+    /// Run the auth script of the profile, which is expected to update the credentials,
+    /// or to print credentials to be written by awsctx if `output` is `credential_process`
+    fn run_auth_script(
+        &self,
+        profile: &str,
+        auth: &AuthConfigs,
+        args: &[String],
+    ) -> Result<(), ctx::CTXError> {
+        let envs = self
+            .configs
+            .get_profile_configs(profile)
+            .map(|p| p.env.clone())
+            .unwrap_or_default();
+        let data = self.template_data(profile, &envs, args);
+        let timeout = self.configs.get_auth_timeout(profile);
+        let log = self.create_log(profile);
+        let log_path = log.as_ref().map(|l| l.path().to_path_buf());
+        let see_log = || {
+            log_path
+                .as_ref()
+                .map(|p| format!(", see the log ({})", p.display()))
+                .unwrap_or_default()
+        };
+        let write_log = log
+            .as_ref()
+            .map(|log| move |stream: &str, line: &str| log.write_line(stream, line));
+        let options = process::OutputOptions {
+            capture_stdout: auth.output() == AuthOutput::CredentialProcess,
+            prefix: self.output_prefix.as_deref(),
+            log: write_log.as_ref().map(|f| f as process::LineLog),
+        };
+        if let Some(log) = &log {
+            log.write_line(
+                "awsctx",
+                &format!("auth of profile ({}): {}", profile, auth.command()),
+            );
+        }
+        let attempts = auth.retries() + 1;
+        let mut attempt = 1;
+        let output = loop {
+            // the script file is removed when the path is dropped after the script exits
+            let (mut command, _script_path) = self.auth_command(profile, auth, &data, args)?;
+            command.envs(&envs);
+            self.close_stdin(&mut command);
+            // scripts writing the credentials by themselves run one by one in parallel auth
+            // not to lose updates by other scripts
+            let _lock = match (&self.output_prefix, auth.output()) {
+                (Some(_), AuthOutput::Inherit) => {
+                    Some(Credentials::lock_credentials(&self.credentials_path)?)
+                }
+                _ => None,
+            };
+            let output =
+                process::run_with_output(&mut command, timeout, &options).map_err(|e| {
+                    ctx::CTXError::InvalidConfigurations {
+                        message: format!(
+                        "failed to execute an auth script of profile ({}), check configurations{}",
+                        profile,
+                        see_log()
+                    ),
+                        source: Some(anyhow!("failed to execute an auth script: {}", e)),
+                    }
+                })?;
+            if let Some(log) = &log {
+                let exit = match &output.exit {
+                    process::Exit::Exited(status) => process::describe_status(status),
+                    process::Exit::TimedOut => "timeout".to_string(),
+                };
+                log.write_line(
+                    "awsctx",
+                    &format!("attempt {}/{} exited with {}", attempt, attempts, exit),
+                );
+            }
+            let status = match output.exit {
+                process::Exit::Exited(status) if status.success() => break output,
+                process::Exit::Exited(status) => status,
+                process::Exit::TimedOut => {
+                    return Err(ctx::CTXError::AuthTimeout {
+                        profile: profile.to_string(),
+                        timeout: timeout.unwrap_or_default(),
+                        log: log_path,
+                        source: None,
+                    })
+                }
+            };
+            if attempt >= attempts || !auth.is_retryable(status.code()) {
+                return Err(ctx::CTXError::AuthFailed {
+                    profile: profile.to_string(),
+                    status: process::describe_status(&status),
+                    attempts: attempt,
+                    stderr: output.stderr_tail,
+                    log: log_path,
+                    source: None,
+                });
+            }
+            let backoff = auth.backoff(attempt);
+            warn!(
+                "auth script of profile ({}) failed with {} (attempt {}/{}), retry in {} seconds",
+                profile,
+                process::describe_status(&status),
+                attempt,
+                attempts,
+                backoff.as_secs()
+            );
+            std::thread::sleep(backoff);
+            attempt += 1;
+        };
+
+        if auth.output() == AuthOutput::CredentialProcess {
+            let credentials =
+                ProfileCredentials::from_credential_process(&String::from_utf8_lossy(&output.stdout))
+                    .map_err(|e| ctx::CTXError::InvalidConfigurations {
+                        message: format!(
+                            "auth script of profile ({}) printed invalid credentials, `output: credential_process` expects JSON of credential_process{}",
+                            profile,
+                            see_log()
+                        ),
+                        source: Some(e),
+                    })?;
+            self.write_credentials(profile, &credentials)?;
+        }
+        Ok(())
+    }
+
+    /// Resolve a profile name or an alias defined in configs to a profile name
+    fn resolve_profile(&self, name: &str) -> Result<String, ctx::CTXError> {
+        let profile = match self.configs.resolve_alias(name)? {
+            Some(profile) if profile != name => profile,
+            _ => return Ok(name.to_string()),
+        };
+        let shadowed = match Credentials::load_credentials(&self.credentials_path) {
+            Ok(creds) => creds.contains_profile(name),
+            // credentials may not exist yet before the first auth
+            Err(ctx::CTXError::CannotReadCredentials { source: _ }) => false,
+            Err(e) => return Err(e),
+        };
+        if shadowed || self.configs.get_profile_configs(name).is_some() {
+            return Err(ctx::CTXError::InvalidAlias {
+                alias: name.to_string(),
                 message: format!(
-                    "failed to execute an auth script of profile ({}), check configurations",
-                    profile
+                    "alias ({}) of profile ({}) shadows the existing profile",
+                    name, profile
                 ),
-                source: Some(anyhow!("failed to run auth script, check output logs")),
+                source: None,
             });
         }
-        self.use_context(profile)
+        Ok(profile.to_string())
+    }
+}
+
+/// Sort contexts by the order, ties are broken by profile names.
+/// The active context comes first unless sorted alphabetically.
+fn sort_contexts(contexts: &mut [ctx::Context], order: ctx::SortOrder, state: &State) {
+    let usage = |c: &ctx::Context| {
+        state
+            .get_profile_state(&c.name)
+            .cloned()
+            .unwrap_or_default()
+    };
+    contexts.sort_by(|a, b| a.name.cmp(&b.name));
+    match order {
+        ctx::SortOrder::Alphabetical => (),
+        ctx::SortOrder::Recent => contexts.sort_by_key(|c| Reverse(usage(c).last_used)),
+        ctx::SortOrder::Frequent => contexts.sort_by_key(|c| {
+            let usage = usage(c);
+            Reverse((usage.use_count, usage.last_used))
+        }),
+    }
+    contexts.sort_by_key(|c| !c.active);
+}
+
+/// Write a script to a temporary file which only the user can read and execute
+fn write_script(script: &str) -> std::io::Result<TempPath> {
+    let mut file = tempfile::Builder::new().prefix("awsctx-auth-").tempfile()?;
+    file.write_all(script.as_bytes())?;
+    file.as_file()
+        .set_permissions(fs::Permissions::from_mode(0o700))?;
+    // close the file not to fail to execute it with a busy text file
+    Ok(file.into_temp_path())
+}
+
+/// Name of the user running awsctx
+fn os_user() -> Option<String> {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("LOGNAME"))
+        .ok()
+        .or_else(passwd_user)
+}
+
+/// Name of the user in the password database, looked up by the reentrant `getpwuid_r`
+/// since auth of profiles runs in parallel threads
+fn passwd_user() -> Option<String> {
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut passwd = std::mem::MaybeUninit::<libc::passwd>::uninit();
+    let mut result = std::ptr::null_mut();
+    loop {
+        let err = unsafe {
+            libc::getpwuid_r(
+                libc::getuid(),
+                passwd.as_mut_ptr(),
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+        match err {
+            libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            0 if !result.is_null() => break,
+            _ => return None,
+        }
+    }
+    // the name points into `buf`, which is alive here
+    unsafe { std::ffi::CStr::from_ptr((*result).pw_name) }
+        .to_str()
+        .ok()
+        .map(String::from)
+}
+
+/// Quote an argument to be embedded in a shell script as a single word
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@,+%".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+impl<P: AsRef<Path>> ctx::CTX for AWS<'_, P> {
+    fn auth(&self, profile: &str, args: &[String]) -> Result<ctx::Context, ctx::CTXError> {
+        let profile = self.resolve_profile(profile)?;
+        self.confirm_activation(&profile)?;
+        let old = self.get_active_context().ok().map(|c| c.name);
+        self.run_hooks(Hook::PreAuth, old.as_deref(), &profile)?;
+        self.authenticate(&profile, args)?;
+        let context = self.switch(&profile)?;
+        self.run_hooks(Hook::PostAuth, old.as_deref(), &profile)?;
+        Ok(context)
+    }
+
+    fn run_auth(&self, profile: &str, args: &[String]) -> Result<ctx::Context, ctx::CTXError> {
+        let profile = self.resolve_profile(profile)?;
+        let old = self.get_active_context().ok().map(|c| c.name);
+        self.run_hooks(Hook::PreAuth, old.as_deref(), &profile)?;
+        self.authenticate(&profile, args)?;
+        self.run_hooks(Hook::PostAuth, old.as_deref(), &profile)?;
+        let creds = Credentials::load_credentials(&self.credentials_path)?;
+        let context = creds.get_profile(&profile).map(|p| self.to_context(&p))?;
+        self.record_usage(&profile);
+        Ok(context)
+    }
+
+    fn list_auth_profiles(&self, group: Option<&str>) -> Result<Vec<String>, ctx::CTXError> {
+        let mut names = self
+            .configs
+            .profiles
+            .keys()
+            .filter(|name| name.as_str() != Configs::DEFAULT_AUTH_COMMAND_KEY)
+            .cloned()
+            .collect::<Vec<_>>();
+        // profiles only in the credentials are authenticated by the default auth
+        match Credentials::load_credentials(&self.credentials_path) {
+            Ok(creds) => names.extend(creds.list_profiles().into_iter().map(|p| p.name)),
+            Err(ctx::CTXError::CannotReadCredentials { source: _ }) => (),
+            Err(e) => return Err(e),
+        }
+        let matcher = group.map(|g| self.configs.group_matcher(g)).transpose()?;
+        names.retain(|name| {
+            self.configs.get_auth_configs(name).is_some()
+                && matcher.as_ref().map(|m| m(name)).unwrap_or(true)
+        });
+        names.sort_unstable();
+        names.dedup();
+        Ok(names)
+    }
+
+    fn run_auth_all(
+        &self,
+        profiles: &[String],
+        args: &[String],
+        jobs: usize,
+    ) -> Vec<ctx::AuthResult> {
+        let run = |aws: &dyn ctx::CTX, profile: &str| {
+            let started = Instant::now();
+            let result = aws.run_auth(profile, args);
+            ctx::AuthResult {
+                profile: profile.to_string(),
+                result,
+                duration: started.elapsed(),
+            }
+        };
+        if jobs <= 1 || profiles.len() <= 1 {
+            return profiles
+                .iter()
+                .map(|profile| {
+                    info!("auth with profile ({})", profile);
+                    run(self, profile)
+                })
+                .collect();
+        }
+
+        let jobs = jobs.min(profiles.len());
+        info!("auth with {} profiles, {} at once", profiles.len(), jobs);
+        let width = profiles.iter().map(|p| p.len()).max().unwrap_or_default();
+        let queue = Mutex::new(profiles.iter().enumerate());
+        let results = Mutex::new(vec![]);
+        thread::scope(|s| {
+            for _ in 0..jobs {
+                // AWS cannot be shared between threads, so each worker has its own one
+                // with the same settings, and usage of profiles is recorded after all the jobs.
+                let configs = Configs::clone(&self.configs);
+                let credentials_path = self.credentials_path.as_ref().to_path_buf();
+                let (lock_dir, log_dir) = (self.lock_dir.clone(), self.log_dir.clone());
+                let (sso_cache_dir, non_interactive) =
+                    (self.sso_cache_dir.clone(), self.non_interactive);
+                let prompter = self.prompter.clone_for_thread();
+                let (queue, results) = (&queue, &results);
+                s.spawn(move || {
+                    let mut aws = AWS::new(Rc::new(configs), credentials_path).map(|mut aws| {
+                        aws.lock_dir = lock_dir;
+                        aws.log_dir = log_dir;
+                        aws.sso_cache_dir = sso_cache_dir;
+                        aws.non_interactive = non_interactive;
+                        aws.prompter = prompter;
+                        aws
+                    });
+                    loop {
+                        let next = queue.lock().unwrap().next();
+                        let Some((i, profile)) = next else { break };
+                        let result = match aws.as_mut() {
+                            Ok(aws) => {
+                                aws.output_prefix = Some(format!("{:width$} | ", profile));
+                                run(aws, profile)
+                            }
+                            Err(e) => ctx::AuthResult {
+                                profile: profile.to_string(),
+                                result: Err(ctx::CTXError::UnexpectedError {
+                                    source: Some(anyhow!("failed to start auth: {}", e)),
+                                }),
+                                duration: Default::default(),
+                            },
+                        };
+                        results.lock().unwrap().push((i, result));
+                    }
+                });
+            }
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(i, _)| *i);
+        results
+            .into_iter()
+            .map(|(_, r)| {
+                if r.result.is_ok() {
+                    self.record_usage(&r.profile);
+                }
+                r
+            })
+            .collect()
     }
 
     fn list_contexts(&self) -> Result<Vec<ctx::Context>, ctx::CTXError> {
@@ -90,59 +1218,121 @@ impl<P: AsRef<Path>> ctx::CTX for AWS<'_, P> {
         Ok(creds
             .list_profiles()
             .into_iter()
-            .map(|p| ctx::Context {
-                name: p.name.to_string(),
-                active: p.default,
-            })
+            .map(|p| self.to_context(&p))
             .collect())
     }
 
+    fn filter_contexts(
+        &self,
+        filter: &ctx::ContextFilter,
+    ) -> Result<Vec<ctx::Context>, ctx::CTXError> {
+        let pattern = filter
+            .pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| ctx::CTXError::InvalidArgument {
+                message: format!(
+                    "invalid filter pattern ({})",
+                    filter.pattern.as_deref().unwrap_or_default()
+                ),
+                source: Some(e.into()),
+            })?;
+        let matcher = filter
+            .group
+            .as_deref()
+            .map(|g| self.configs.group_matcher(g))
+            .transpose()?;
+
+        let mut contexts = self.list_contexts()?;
+        contexts.retain(|c| {
+            pattern
+                .as_ref()
+                .map(|r| r.is_match(&c.name))
+                .unwrap_or(true)
+                && matcher.as_ref().map(|m| m(&c.name)).unwrap_or(true)
+        });
+        sort_contexts(&mut contexts, filter.sort, &self.load_state());
+        Ok(contexts)
+    }
+
     fn get_active_context(&self) -> Result<ctx::Context, ctx::CTXError> {
         let creds = Credentials::load_credentials(&self.credentials_path)?;
-        creds.get_default_profile().map(|p| ctx::Context {
-            name: p.name.to_string(),
-            active: p.default,
-        })
+        creds.get_default_profile().map(|p| self.to_context(&p))
     }
 
     fn use_context(&self, name: &str) -> Result<ctx::Context, ctx::CTXError> {
-        let mut creds = Credentials::load_credentials(&self.credentials_path)?;
-        let profile = creds.set_default_profile(name)?;
-        creds.dump_credentials(&self.credentials_path)?;
-        Ok(ctx::Context {
-            name: profile.name.to_string(),
-            active: profile.default,
-        })
+        let name = self.resolve_profile(name)?;
+        self.confirm_activation(&name)?;
+        self.switch(&name)
+    }
+
+    fn describe_context(&self, name: &str) -> Result<ctx::ContextDetails, ctx::CTXError> {
+        let name = self.resolve_profile(name)?;
+        let creds = Credentials::load_credentials(&self.credentials_path)?;
+        let profile = creds.get_profile(&name)?;
+        Ok(self.to_context_details(&profile, &self.load_state()))
     }
 
-    fn use_context_interactive(
+    fn describe_contexts(
         &self,
-        skim_options: SkimOptions,
-    ) -> Result<ctx::Context, ctx::CTXError> {
-        let (tx_item, rx_item): (SkimItemSender, SkimItemReceiver) = unbounded();
-        // skim shows reverse order
-        for context in self.list_contexts()?.into_iter().rev() {
-            tx_item
-                .send(Arc::new(context))
-                .context("failed to send an item to skim")
-                .map_err(|e| ctx::CTXError::UnexpectedError { source: Some(e) })?;
-        }
-        drop(tx_item);
-
-        let selected_items = Skim::run_with(&skim_options, Some(rx_item))
-            .map(|out| match out.final_key {
-                Key::Enter => Ok(out.selected_items),
-                _ => Err(ctx::CTXError::NoContextIsSelected { source: None }),
-            })
-            .unwrap_or(Ok(Vec::new()))?;
-        let item = selected_items
-            .get(0)
-            .ok_or(ctx::CTXError::NoContextIsSelected { source: None })?;
-        let context = (*item).as_any().downcast_ref::<ctx::Context>().ok_or(
-            ctx::CTXError::UnexpectedError {
-                source: Some(anyhow!("unexpected error")),
-            },
-        )?;
-        self.use_context(&context.name)
+        names: &[String],
+    ) -> Result<Vec<ctx::ContextDetails>, ctx::CTXError> {
+        let creds = Credentials::load_credentials(&self.credentials_path)?;
+        let state = self.load_state();
+        Ok(names
+            .iter()
+            .filter_map(|name| creds.get_profile(name).ok())
+            .map(|profile| self.to_context_details(&profile, &state))
+            .collect())
+    }
+
+    fn delete_context(&self, name: &str) -> Result<ctx::Context, ctx::CTXError> {
+        let name = self.resolve_profile(name)?;
+        // check existence before asking
+        Credentials::load_credentials(&self.credentials_path)?.get_profile(&name)?;
+        let message = format!(
+            "profile ({}) will be deleted from the credentials, type the profile name to continue",
+            name
+        );
+        if !self.prompter.confirm(&message, &[name.to_string()])? {
+            return Err(ctx::CTXError::NotConfirmed {
+                profile: name,
+                source: None,
+            });
+        }
+        let profile = Credentials::update_credentials(&self.credentials_path, |creds| {
+            creds.remove_profile(&name)
+        })?;
+        Ok(self.to_context(&profile))
+    }
+
+    fn exec(&self, name: &str, command: &[String]) -> Result<i32, ctx::CTXError> {
+        let name = self.resolve_profile(name)?;
+        let creds = Credentials::load_credentials(&self.credentials_path)?;
+        let profile = creds.get_profile(&name)?;
+        self.confirm_protected(&profile.name)?;
+        let (program, args) =
+            command
+                .split_first()
+                .ok_or_else(|| ctx::CTXError::UnexpectedError {
+                    source: Some(anyhow!("no command is specified")),
+                })?;
+
+        let mut cmd = Command::new(program);
+        cmd.args(args).env("AWS_PROFILE", &profile.name);
+        if let Some(configs) = self.configs.get_profile_configs(&profile.name) {
+            if let Some(region) = &configs.region {
+                cmd.env("AWS_REGION", region)
+                    .env("AWS_DEFAULT_REGION", region);
+            }
+            cmd.envs(&configs.env);
+        }
+        let status = cmd
+            .status()
+            .context(format!("failed to execute a command: {}", program))
+            .map_err(|e| ctx::CTXError::UnexpectedError { source: Some(e) })?;
+        // a process terminated by a signal has no exit code
+        Ok(status.code().unwrap_or(1))
     }
 }
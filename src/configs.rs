@@ -1,18 +1,22 @@
 use dirs::home_dir;
 use maplit::hashmap;
-use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
+use std::time::Duration;
 use std::{collections::HashMap, path::Path};
+use std::{fmt, fs};
 
 use anyhow::{anyhow, Context, Result};
 use config::{Config, File, FileFormat};
 use once_cell::sync::Lazy;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use tuikit::key::from_keyname;
 
 use crate::ctx;
 
 type ProfileName = String;
+type GroupName = String;
 type AuthScript = String;
 
 pub static CONFIGS_PATH: Lazy<PathBuf> = Lazy::new(|| {
@@ -21,20 +25,735 @@ pub static CONFIGS_PATH: Lazy<PathBuf> = Lazy::new(|| {
     path
 });
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+/// Auth configuration of a profile, which is either a script or a map with options
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(untagged)]
+// configurations are loaded only once, so the size of variants does not matter
+#[allow(clippy::large_enum_variant)]
+pub enum AuthConfigs {
+    Script(AuthScript),
+    Options(AuthOptions),
+}
+
+// NOTE: deserialize by hand since errors of untagged enums do not tell which field is wrong
+impl<'de> Deserialize<'de> for AuthConfigs {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AuthConfigsVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for AuthConfigsVisitor {
+            type Value = AuthConfigs;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter
+                    .write_str("an auth script or a map with `script`, `argv`, `assume_role`, `session_token` or `sso`")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(AuthConfigs::Script(value.to_string()))
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<Self::Value, A::Error> {
+                AuthOptions::deserialize(serde::de::value::MapAccessDeserializer::new(map))
+                    .map(AuthConfigs::Options)
+            }
+        }
+
+        deserializer.deserialize_any(AuthConfigsVisitor)
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct AuthOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<AuthScript>,
+    /// Command to run the script with, e.g. `bash` or `python3`, which is given a path of the script.
+    /// `sh -c` is used if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpreter: Option<String>,
+    /// Command and arguments executed directly without a shell instead of `script`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub argv: Vec<String>,
+    /// Seconds to wait for the script, which overrides the global one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(default, skip_serializing_if = "AuthOutput::is_default")]
+    pub output: AuthOutput,
+    /// Times to rerun the script after failures
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// Seconds to wait before the first retry, which is doubled for each retry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backoff: Option<u64>,
+    /// Exit codes of the script which are not retried, e.g. for a wrong password
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub non_retryable_exit_codes: Vec<i32>,
+    /// Role assumed by awsctx itself instead of running a script
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assume_role: Option<AssumeRoleConfigs>,
+    /// Session credentials obtained by awsctx itself instead of running a script
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_token: Option<SessionTokenConfigs>,
+    /// Role credentials obtained by awsctx itself with a login to IAM Identity Center
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sso: Option<SsoConfigs>,
+}
+
+/// Role assumed with credentials of the source profile by calling STS AssumeRole
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct AssumeRoleConfigs {
+    /// Profile whose credentials are used, which may assume a role by itself to chain roles
+    pub source_profile: String,
+    pub role_arn: String,
+    /// Template of the role session name, `awsctx-{{user}}` by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_name: Option<String>,
+    /// Seconds of the session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    /// Serial number of the MFA device, whose code is asked on auth
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mfa_serial: Option<String>,
+}
+
+impl AssumeRoleConfigs {
+    pub const DEFAULT_SESSION_NAME: &'static str = "awsctx-{{user}}";
+}
+
+/// Session credentials obtained with long-term keys of the source profile by calling STS GetSessionToken
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SessionTokenConfigs {
+    /// Profile which has long-term access keys of an IAM user
+    pub source_profile: String,
+    /// Serial number of the MFA device, whose code is asked on auth
+    pub mfa_serial: String,
+    /// Seconds of the session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+}
+
+/// Role credentials obtained with an access token of IAM Identity Center (SSO) by calling GetRoleCredentials
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SsoConfigs {
+    /// URL of the AWS access portal
+    pub start_url: String,
+    /// Region of IAM Identity Center, which may differ from the region of the profile
+    pub region: String,
+    pub account_id: String,
+    pub role_name: String,
+}
+
+/// How credentials are obtained from an auth script
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", try_from = "String")]
+pub enum AuthOutput {
+    /// The script writes credentials by itself, and its stdout is shown as is
+    #[default]
+    Inherit,
+    /// The script prints credentials in the JSON format of `credential_process` to stdout
+    CredentialProcess,
+}
+
+impl AuthOutput {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+impl TryFrom<String> for AuthOutput {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "inherit" => Ok(AuthOutput::Inherit),
+            "credential_process" => Ok(AuthOutput::CredentialProcess),
+            _ => Err(format!(
+                "unknown output ({}) of auth, expected one of inherit or credential_process",
+                value
+            )),
+        }
+    }
+}
+
+impl AuthConfigs {
+    pub fn script(&self) -> Option<&AuthScript> {
+        match self {
+            AuthConfigs::Script(script) => Some(script),
+            AuthConfigs::Options(options) => options.script.as_ref(),
+        }
+    }
+
+    pub fn interpreter(&self) -> Option<&str> {
+        match self {
+            AuthConfigs::Script(_) => None,
+            AuthConfigs::Options(options) => options.interpreter.as_deref(),
+        }
+    }
+
+    pub fn argv(&self) -> &[String] {
+        match self {
+            AuthConfigs::Script(_) => &[],
+            AuthConfigs::Options(options) => &options.argv,
+        }
+    }
+
+    pub fn retries(&self) -> u32 {
+        match self {
+            AuthConfigs::Script(_) => 0,
+            AuthConfigs::Options(options) => options.retries.unwrap_or_default(),
+        }
+    }
+
+    /// Time to wait before the retry of the attempt, starting from 1
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = match self {
+            AuthConfigs::Script(_) => None,
+            AuthConfigs::Options(options) => options.backoff,
+        };
+        Duration::from_secs(backoff.unwrap_or(1))
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+    }
+
+    pub fn is_retryable(&self, exit_code: Option<i32>) -> bool {
+        match (self, exit_code) {
+            (AuthConfigs::Options(options), Some(code)) => {
+                !options.non_retryable_exit_codes.contains(&code)
+            }
+            _ => true,
+        }
+    }
+
+    pub fn assume_role(&self) -> Option<&AssumeRoleConfigs> {
+        match self {
+            AuthConfigs::Script(_) => None,
+            AuthConfigs::Options(options) => options.assume_role.as_ref(),
+        }
+    }
+
+    pub fn session_token(&self) -> Option<&SessionTokenConfigs> {
+        match self {
+            AuthConfigs::Script(_) => None,
+            AuthConfigs::Options(options) => options.session_token.as_ref(),
+        }
+    }
+
+    pub fn sso(&self) -> Option<&SsoConfigs> {
+        match self {
+            AuthConfigs::Script(_) => None,
+            AuthConfigs::Options(options) => options.sso.as_ref(),
+        }
+    }
+
+    /// Command of the auth to show, which is the script, argv joined by spaces,
+    /// or a description of the auth by awsctx itself
+    pub fn command(&self) -> String {
+        if let Some(assume_role) = self.assume_role() {
+            return format!(
+                "assume role ({}) with profile ({})",
+                assume_role.role_arn, assume_role.source_profile
+            );
+        }
+        if let Some(session_token) = self.session_token() {
+            return format!(
+                "get session token with profile ({})",
+                session_token.source_profile
+            );
+        }
+        if let Some(sso) = self.sso() {
+            return format!(
+                "get role credentials of role ({}) in account ({}) by IAM Identity Center ({})",
+                sso.role_name, sso.account_id, sso.start_url
+            );
+        }
+        self.script()
+            .cloned()
+            .unwrap_or_else(|| self.argv().join(" "))
+    }
+
+    pub fn timeout(&self) -> Option<u64> {
+        match self {
+            AuthConfigs::Script(_) => None,
+            AuthConfigs::Options(options) => options.timeout,
+        }
+    }
+
+    pub fn output(&self) -> AuthOutput {
+        match self {
+            AuthConfigs::Script(_) => AuthOutput::default(),
+            AuthConfigs::Options(options) => options.output,
+        }
+    }
+}
+
+impl AuthOptions {
+    /// Test whether options only for auth scripts are set
+    fn has_script_options(&self) -> bool {
+        self.interpreter.is_some()
+            || !self.output.is_default()
+            || self.retries.is_some()
+            || self.backoff.is_some()
+            || !self.non_retryable_exit_codes.is_empty()
+    }
+
+    fn validate(&self, profile: &str) -> Result<(), ctx::CTXError> {
+        let kinds = [
+            self.script.is_some(),
+            !self.argv.is_empty(),
+            self.assume_role.is_some(),
+            self.session_token.is_some(),
+            self.sso.is_some(),
+        ];
+        // auth by awsctx itself without a script
+        let native = match (&self.assume_role, &self.session_token, &self.sso) {
+            (Some(_), _, _) => Some("assume_role"),
+            (_, Some(_), _) => Some("session_token"),
+            (_, _, Some(_)) => Some("sso"),
+            _ => None,
+        };
+        let message = match (&self.script, &self.interpreter) {
+            _ if kinds.iter().filter(|k| **k).count() != 1 => format!(
+                "auth of profile ({}) must have one of `script`, `argv`, `assume_role`, `session_token` or `sso`",
+                profile
+            ),
+            _ if self
+                .session_token
+                .as_ref()
+                .is_some_and(|s| s.source_profile == profile) =>
+            {
+                format!(
+                    "`source_profile` of profile ({}) must be another profile not to overwrite its long-term keys",
+                    profile
+                )
+            }
+            _ if native.is_some() && self.has_script_options() => format!(
+                "options of auth scripts of profile ({}) cannot be used with `{}`",
+                profile,
+                native.unwrap_or_default()
+            ),
+            (None, Some(_)) => format!(
+                "`interpreter` of profile ({}) cannot be used with `argv`",
+                profile
+            ),
+            (Some(_), Some(interpreter)) if interpreter.trim().is_empty() => {
+                format!("`interpreter` of profile ({}) is empty", profile)
+            }
+            _ => return Ok(()),
+        };
+        Err(ctx::CTXError::InvalidConfigurations {
+            message,
+            source: None,
+        })
+    }
+}
+
+impl From<AuthScript> for AuthConfigs {
+    fn from(script: AuthScript) -> Self {
+        AuthConfigs::Script(script)
+    }
+}
+
+/// Auth configurations applied to all the profiles
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GlobalAuthConfigs {
+    /// Seconds to wait for auth scripts, no timeout if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Fail to render auth scripts with undefined variables instead of rendering empty strings
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict: bool,
+    /// Seconds to wait for auth of the same profile by another process before ignoring its lock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock_timeout: Option<u64>,
+    #[serde(default, skip_serializing_if = "EndpointsConfigs::is_default")]
+    pub endpoints: EndpointsConfigs,
+}
+
+impl GlobalAuthConfigs {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Endpoints of AWS APIs called by awsctx, which can be overridden e.g. by a local mock
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointsConfigs {
+    /// Endpoint of STS, the regional endpoint of the profile is used if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sts: Option<String>,
+    /// Endpoint of IAM Identity Center OIDC, the one of the region of `sso` is used if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sso_oidc: Option<String>,
+    /// Endpoint of the AWS access portal, the one of the region of `sso` is used if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sso_portal: Option<String>,
+}
+
+impl EndpointsConfigs {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfigs {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<AuthConfigs>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    /// Require confirmation before activating the profile
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub protected: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Variables for the auth script, which override the global ones
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub vars: HashMap<String, String>,
+    /// Hooks for the profile, which run after the global ones
+    #[serde(default, skip_serializing_if = "HooksConfigs::is_default")]
+    pub hooks: HooksConfigs,
+}
+
+/// Scripts run before and after switching or authenticating a profile
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfigs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_use: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_use: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_auth: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_auth: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PreUse,
+    PostUse,
+    PreAuth,
+    PostAuth,
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Hook::PreUse => "pre_use",
+            Hook::PostUse => "post_use",
+            Hook::PreAuth => "pre_auth",
+            Hook::PostAuth => "post_auth",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl HooksConfigs {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn get(&self, hook: Hook) -> Option<&String> {
+        match hook {
+            Hook::PreUse => self.pre_use.as_ref(),
+            Hook::PostUse => self.post_use.as_ref(),
+            Hook::PreAuth => self.pre_auth.as_ref(),
+            Hook::PostAuth => self.post_auth.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GroupConfigs {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<ProfileName>,
+    /// Regular expression to match profile names which belong to the group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+}
+
+impl GroupConfigs {
+    pub fn matcher(&self) -> Result<impl Fn(&str) -> bool + '_, regex::Error> {
+        let pattern = self.pattern.as_deref().map(Regex::new).transpose()?;
+        Ok(move |profile: &str| {
+            self.profiles.iter().any(|p| p == profile)
+                || pattern
+                    .as_ref()
+                    .map(|r| r.is_match(profile))
+                    .unwrap_or_default()
+        })
+    }
+}
+
+/// Keys of actions in the interactive finder, the format follows `--bind` option of skim
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FinderBindings {
+    pub auth: String,
+    pub describe: String,
+    pub delete: String,
+    pub copy: String,
+}
+
+impl Default for FinderBindings {
+    fn default() -> Self {
+        Self {
+            auth: "ctrl-a".to_string(),
+            describe: "ctrl-d".to_string(),
+            delete: "ctrl-x".to_string(),
+            copy: "ctrl-y".to_string(),
+        }
+    }
+}
+
+/// Actions for the highlighted profile in the interactive finder besides switching
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinderAction {
+    Auth,
+    Describe,
+    Delete,
+    Copy,
+}
+
+impl fmt::Display for FinderAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FinderAction::Auth => write!(f, "auth"),
+            FinderAction::Describe => write!(f, "describe"),
+            FinderAction::Delete => write!(f, "delete"),
+            FinderAction::Copy => write!(f, "copy"),
+        }
+    }
+}
+
+impl FinderBindings {
+    /// List pairs of an action and its key
+    pub fn keys(&self) -> Vec<(FinderAction, &str)> {
+        vec![
+            (FinderAction::Auth, &self.auth),
+            (FinderAction::Describe, &self.describe),
+            (FinderAction::Delete, &self.delete),
+            (FinderAction::Copy, &self.copy),
+        ]
+    }
+
+    fn validate(&self) -> Result<(), ctx::CTXError> {
+        let bindings = self.keys();
+        for (i, (action, key)) in bindings.iter().enumerate() {
+            let message = if from_keyname(key).is_none() {
+                format!(
+                    "invalid key ({}) for action ({}) of the finder",
+                    key, action
+                )
+            } else if let Some((other, _)) = bindings[..i].iter().find(|(_, k)| k == key) {
+                format!(
+                    "key ({}) is bound to both actions ({}) and ({}) of the finder",
+                    key, other, action
+                )
+            } else {
+                continue;
+            };
+            return Err(ctx::CTXError::InvalidConfigurations {
+                message,
+                source: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Case sensitivity of queries in the interactive finder
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase", try_from = "String")]
+pub enum FinderCase {
+    Respect,
+    Ignore,
+    /// Case sensitive only if a query contains uppercase characters
+    #[default]
+    Smart,
+}
+
+impl TryFrom<String> for FinderCase {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "respect" => Ok(FinderCase::Respect),
+            "ignore" => Ok(FinderCase::Ignore),
+            "smart" => Ok(FinderCase::Smart),
+            _ => Err(format!(
+                "unknown case ({}), expected one of respect, ignore or smart",
+                value
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FinderConfigs {
+    /// Height of the finder in lines or a percentage of the terminal, e.g. `30%`
+    pub height: String,
+    /// Show the prompt at the top and profiles from top to bottom
+    pub reverse: bool,
+    pub prompt: String,
+    /// Color scheme in the format of `--color` option of skim, e.g. `light` or `fg:252,bg:234`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    pub preview: bool,
+    /// Position and size of the preview window, e.g. `right:50%` or `down:10`
+    pub preview_window: String,
+    pub case: FinderCase,
+    pub sort: ctx::SortOrder,
+    pub bindings: FinderBindings,
+    /// Command of an external finder such as `fzf`, which reads profile names from stdin
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+}
+
+impl Default for FinderConfigs {
+    fn default() -> Self {
+        Self {
+            height: "30%".to_string(),
+            reverse: false,
+            prompt: "> ".to_string(),
+            color: None,
+            preview: true,
+            preview_window: "right:50%".to_string(),
+            case: FinderCase::default(),
+            sort: ctx::SortOrder::default(),
+            bindings: FinderBindings::default(),
+            backend: None,
+        }
+    }
+}
+
+static FINDER_HEIGHT_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[1-9]\d*%?$").unwrap());
+static FINDER_PREVIEW_WINDOW_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(up|down|left|right)(:[1-9]\d*%?)?(:wrap)?$").unwrap());
+/// Each comma separated item of `--color` of skim, which is a base scheme or `name:color`
+/// with a 256 color number or `#rrggbb`
+static FINDER_COLOR_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^((dark|default|light|molokai|16|bw|empty)|(fg|bg|matched|hl|matched_bg|current|fg\+|current_bg|bg\+|current_match|hl\+|current_match_bg|query|query_bg|spinner|info|prompt|cursor|pointer|selected|marker|header|border):(25[0-5]|2[0-4]\d|1?\d?\d|#[0-9a-fA-F]{6}))$",
+    )
+    .unwrap()
+});
+
+impl FinderConfigs {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    fn validate(&self) -> Result<(), ctx::CTXError> {
+        let message = if !FINDER_HEIGHT_PATTERN.is_match(&self.height) {
+            format!(
+                "invalid height ({}) of the finder, specify lines (e.g. 20) or a percentage (e.g. 30%)",
+                self.height
+            )
+        } else if !FINDER_PREVIEW_WINDOW_PATTERN.is_match(&self.preview_window) {
+            format!(
+                "invalid preview window ({}) of the finder, specify a position (up, down, left or right) and an optional size (e.g. right:50%)",
+                self.preview_window
+            )
+        } else if let Some(color) = self
+            .color
+            .as_deref()
+            .filter(|c| !c.split(',').all(|item| FINDER_COLOR_PATTERN.is_match(item)))
+        {
+            format!(
+                "invalid color ({}) of the finder, specify a base scheme (e.g. light) and `name:color` separated by commas (e.g. light,fg:252,bg:#1c1c1c)",
+                color
+            )
+        } else {
+            return self.bindings.validate();
+        };
+        Err(ctx::CTXError::InvalidConfigurations {
+            message,
+            source: None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
 pub struct Configs {
-    pub auth_commands: HashMap<ProfileName, AuthScript>,
+    #[serde(default)]
+    pub profiles: HashMap<ProfileName, ProfileConfigs>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub groups: HashMap<GroupName, GroupConfigs>,
+    #[serde(default, skip_serializing_if = "FinderConfigs::is_default")]
+    pub finder: FinderConfigs,
+    #[serde(default, skip_serializing_if = "GlobalAuthConfigs::is_default")]
+    pub auth: GlobalAuthConfigs,
+    /// Variables for auth scripts of all the profiles
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub vars: HashMap<String, String>,
+    /// Hooks for all the profiles
+    #[serde(default, skip_serializing_if = "HooksConfigs::is_default")]
+    pub hooks: HooksConfigs,
+}
+
+/// Layout of configs.yaml before `profiles` was introduced
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LegacyConfigs {
+    auth_commands: HashMap<ProfileName, AuthScript>,
 }
 
 impl Default for Configs {
     fn default() -> Self {
         Self {
-            auth_commands: hashmap! {
-            Self::DEFAULT_AUTH_COMMAND_KEY.to_string()  => r#"echo "This is default configuration for auth commands."
+            profiles: hashmap! {
+                Self::DEFAULT_AUTH_COMMAND_KEY.to_string() => ProfileConfigs {
+                    auth: Some(r#"echo "This is default configuration for auth commands."
 echo "You can edit this configuration on ~/.awsctx/configs.yaml according to your needs."
 aws configure --profile {{profile}}
-"#.to_string(),
+"#.to_string().into()),
+                    ..Default::default()
                 },
+            },
+            ..Self::empty()
+        }
+    }
+}
+
+impl From<LegacyConfigs> for Configs {
+    fn from(legacy: LegacyConfigs) -> Self {
+        Self {
+            profiles: legacy
+                .auth_commands
+                .into_iter()
+                .map(|(name, script)| {
+                    (
+                        name,
+                        ProfileConfigs {
+                            auth: Some(script.into()),
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            ..Default::default()
         }
     }
 }
@@ -43,23 +762,286 @@ impl Configs {
     const CONFIGS_DESCRIPTIONS: &'static str = r#"# # Configurations for awsctx
 # # You can manually edit configurations according to the following usage
 
-# # To use subcommand `auth` or `refresh`, fill the below configs for each profile.
-# auth_commands:
+# # Configurations for each profile are placed under `profiles`.
+# profiles:
 #   # configuration for `foo` profile with aws configure
-#   foo: |
+#   foo:
+#     # To use subcommand `auth` or `refresh`, fill `auth` for the profile.
 #     # you can use pre-defined parameter `{{profile}}` which is replaced by key of this block
 #     # In this case, `{{profile}}` is replaced by `foo`
-#     aws configure --profile {{profile}}
+#     # `{{region}}`, `{{account_id}}`, `vars`, `{{env.NAME}}`, `{{user}}` (the OS user)
+#     # and `{{args}}` (arguments after `--` of `awsctx auth`) are also available
+#     auth: |
+#       aws configure --profile {{profile}}
 #   # configuration for `bar` profile with [onelogin-aws-cli](https://github.com/physera/onelogin-aws-cli)
-#   bar: |
-#     # In this case, name of one-login configuration is same as `profile`
-#     onelogin-aws-login -C {{profile}} --profile {{profile}} -u user@example.com
+#   bar:
+#     # In this case, the app id of one-login and the user are given by `vars`
+#     auth:
+#       script: |
+#         onelogin-aws-login -C {{app_id}} --profile {{profile}} -u {{username}} {{args}}
+#       # command to run the script with a path of the script, e.g. `bash` or `python3`,
+#       # `sh -c` is used by default, and a script starting with a shebang is executed directly
+#       interpreter: bash
+#       # seconds to wait for the script, the script is terminated after the timeout
+#       timeout: 300
+#       # times to rerun the script after failures, waiting `backoff` seconds (doubled for each retry)
+#       retries: 2
+#       backoff: 1
+#       # exit codes which are not retried, e.g. for a wrong password
+#       non_retryable_exit_codes: [2]
+#       # `inherit` (default) if the script writes credentials by itself, or
+#       # `credential_process` if the script prints credentials in the JSON format of credential_process
+#       # (https://docs.aws.amazon.com/cli/latest/userguide/cli-configure-sourcing-external.html),
+#       # then awsctx writes them to the profile
+#       output: inherit
+#     # optional attributes of the profile
+#     region: ap-northeast-1
+#     description: production account
+#     tags: [prod]
+#     # environment variables passed to the auth script
+#     env:
+#       ONELOGIN_REGION: us
+#     # ask to type the profile name or the account id before activating the profile
+#     account_id: "123456789012"
+#     protected: true
+#     aliases: [prod]
+#     # variables for the auth script, which override global `vars`
+#     vars:
+#       app_id: "123456"
+#     # hooks for the profile, which run after global `hooks`
+#     hooks:
+#       post_use: kubectl config use-context prod
+#   # configuration for `baz` profile with a command executed directly without a shell
+#   baz:
+#     auth:
+#       # each element is rendered separately, and `{{args}}` as an element is expanded to the arguments
+#       argv: [aws, sso, login, --profile, "{{profile}}", "{{args}}"]
+#   # configuration for `qux` profile assuming a role by awsctx itself without AWS CLI
+#   qux:
+#     auth:
+#       assume_role:
+#         # profile whose credentials are used, which may also assume a role to chain roles
+#         source_profile: foo
+#         role_arn: arn:aws:iam::123456789012:role/admin
+#         # template of the role session name, `awsctx-{{user}}` by default
+#         session_name: "awsctx-{{user}}"
+#         # optional seconds of the session and external id
+#         duration: 3600
+#         external_id: example
+#         # optional serial number of the MFA device, whose code is asked on auth
+#         mfa_serial: arn:aws:iam::123456789012:mfa/user
+#   # configuration for `quux` profile with MFA session credentials by long-term keys of `quux-long-term` profile
+#   quux:
+#     auth:
+#       session_token:
+#         source_profile: quux-long-term
+#         # serial number of the MFA device, whose code is asked on auth
+#         mfa_serial: arn:aws:iam::123456789012:mfa/user
+#         # optional seconds of the session
+#         duration: 43200
+#   # configuration for `corge` profile with role credentials by a login to IAM Identity Center (SSO).
+#   # The access token is cached in ~/.aws/sso/cache as AWS CLI does, and a login is asked when it expires
+#   corge:
+#     auth:
+#       sso:
+#         # URL of the AWS access portal and the region of IAM Identity Center
+#         start_url: https://my-sso-portal.awsapps.com/start
+#         region: us-east-1
+#         account_id: "123456789012"
+#         role_name: AdministratorAccess
 #   # default configuration for profiles without auth configuration
-#   __default: |
-#     aws configure --profile {{profile}}
+#   __default:
+#     auth: |
+#       aws configure --profile {{profile}}
+# # Groups of profiles to narrow `list-contexts` and the interactive finder by `--group`
+# groups:
+#   prod:
+#     # profiles which belong to the group explicitly
+#     profiles: [bar]
+#     # regular expression to match profile names
+#     pattern: ^acme-prod-
+# # Auth configurations for all the profiles
+# auth:
+#   # seconds to wait for auth scripts, no timeout by default
+#   timeout: 600
+#   # fail on undefined variables in auth scripts instead of rendering empty strings
+#   strict: false
+#   # seconds to wait for auth of the same profile running in another terminal, 600 by default.
+#   # Its result is reused if it succeeds, and its lock is ignored after the timeout
+#   lock_timeout: 600
+#   # endpoints of AWS APIs called by awsctx, e.g. a local mock.
+#   # The regional endpoint for `region` of the profile (or the global one) is used by default
+#   endpoints:
+#     sts: https://sts.ap-northeast-1.amazonaws.com
+#     # endpoints of IAM Identity Center OIDC and the AWS access portal for `region` of `sso` by default
+#     sso_oidc: https://oidc.us-east-1.amazonaws.com
+#     sso_portal: https://portal.sso.us-east-1.amazonaws.com
+# # Variables for auth scripts of all the profiles
+# vars:
+#   username: user@example.com
+# # Scripts run before and after `use-context` (pre_use, post_use) and `auth` (pre_auth, post_auth),
+# # with `{{old_profile}}` and `{{new_profile}}` (also $AWSCTX_OLD_PROFILE and $AWSCTX_NEW_PROFILE).
+# # A failing `pre_*` hook aborts the operation
+# hooks:
+#   post_use: tmux refresh-client -S
+# # Configurations of the interactive finder
+# finder:
+#   # lines (e.g. 20) or a percentage of the terminal
+#   height: 30%
+#   # show the prompt at the top
+#   reverse: false
+#   prompt: "> "
+#   # color scheme in the format of `--color` option of skim
+#   color: dark
+#   preview: true
+#   # up, down, left or right with an optional size
+#   preview_window: right:50%
+#   # respect, ignore or smart
+#   case: smart
+#   # alphabetical, recent or frequent
+#   sort: alphabetical
+#   # keys of actions for the highlighted profile, `enter` switches to the profile
+#   bindings:
+#     auth: ctrl-a
+#     describe: ctrl-d
+#     delete: ctrl-x
+#     # print the profile name to stdout
+#     copy: ctrl-y
+#   # external finder reading profile names from stdin instead of the built-in finder,
+#   # the built-in finder falls back to a numbered menu if no terminal is available
+#   backend: fzf --multi
 "#;
 
     pub const DEFAULT_AUTH_COMMAND_KEY: &'static str = "__default";
+    pub const DEFAULT_LOCK_TIMEOUT_SECS: u64 = 600;
+
+    pub fn get_profile_configs(&self, profile: &str) -> Option<&ProfileConfigs> {
+        self.profiles.get(profile)
+    }
+
+    /// Find a profile name which has the alias, returns `None` if no profile has it
+    pub fn resolve_alias(&self, alias: &str) -> Result<Option<&str>, ctx::CTXError> {
+        let mut profiles = self
+            .profiles
+            .iter()
+            .filter(|(_, p)| p.aliases.iter().any(|a| a == alias))
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<&str>>();
+        profiles.sort_unstable();
+        match profiles.as_slice() {
+            [] => Ok(None),
+            [profile] => Ok(Some(profile)),
+            _ => Err(ctx::CTXError::InvalidAlias {
+                alias: alias.to_string(),
+                message: format!(
+                    "alias ({}) is ambiguous, it is defined for profiles ({})",
+                    alias,
+                    profiles.join(", ")
+                ),
+                source: None,
+            }),
+        }
+    }
+
+    /// Build a predicate which tests whether a profile belongs to the group
+    pub fn group_matcher(&self, group: &str) -> Result<impl Fn(&str) -> bool + '_, ctx::CTXError> {
+        self.groups
+            .get(group)
+            .ok_or_else(|| ctx::CTXError::NoSuchGroup {
+                group: group.to_string(),
+                source: None,
+            })?
+            .matcher()
+            .map_err(|e| ctx::CTXError::InvalidConfigurations {
+                message: format!(
+                    "invalid pattern of group ({}), check your configurations (~/.awsctx/configs.yaml)",
+                    group
+                ),
+                source: Some(e.into()),
+            })
+    }
+
+    /// List names of groups which the profile belongs to
+    pub fn groups_of(&self, profile: &str) -> Vec<&str> {
+        let mut groups = self
+            .groups
+            .iter()
+            .filter(|(_, g)| g.matcher().map(|m| m(profile)).unwrap_or_default())
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<&str>>();
+        groups.sort_unstable();
+        groups
+    }
+
+    pub fn get_auth_configs(&self, profile: &str) -> Option<&AuthConfigs> {
+        self.get_profile_configs(profile)
+            .and_then(|p| p.auth.as_ref())
+            // fallback to default configuration if a command for the profile is not found
+            .or_else(|| {
+                self.get_profile_configs(Self::DEFAULT_AUTH_COMMAND_KEY)
+                    .and_then(|p| p.auth.as_ref())
+            })
+    }
+
+    /// Variables for the auth script of the profile, merged with the global ones
+    pub fn get_vars(&self, profile: &str) -> HashMap<String, String> {
+        let mut vars = self.vars.clone();
+        if let Some(p) = self.get_profile_configs(profile) {
+            vars.extend(p.vars.clone());
+        }
+        vars
+    }
+
+    /// Scripts of the hook for the profile, the global one runs first
+    pub fn get_hooks(&self, profile: &str, hook: Hook) -> Vec<&String> {
+        let mut hooks = vec![];
+        hooks.extend(self.hooks.get(hook));
+        if let Some(p) = self.get_profile_configs(profile) {
+            hooks.extend(p.hooks.get(hook));
+        }
+        hooks
+    }
+
+    /// Timeout of the lock of auth, after which the lock is regarded as stale
+    pub fn get_lock_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.auth
+                .lock_timeout
+                .unwrap_or(Self::DEFAULT_LOCK_TIMEOUT_SECS),
+        )
+    }
+
+    /// Timeout of the auth script for the profile, the global timeout is used if not configured
+    pub fn get_auth_timeout(&self, profile: &str) -> Option<Duration> {
+        self.get_auth_configs(profile)
+            .and_then(|a| a.timeout())
+            .or(self.auth.timeout)
+            .map(Duration::from_secs)
+    }
+
+    fn validate(&self) -> Result<(), ctx::CTXError> {
+        for (name, p) in self.profiles.iter() {
+            if let Some(AuthConfigs::Options(options)) = &p.auth {
+                options.validate(name)?;
+            }
+        }
+        for name in self.groups.keys() {
+            self.group_matcher(name).map(|_| ())?;
+        }
+        self.finder.validate()
+    }
+
+    /// Configurations without any profiles, unlike [`Configs::default`] which has the default auth
+    pub fn empty() -> Self {
+        Self {
+            profiles: HashMap::new(),
+            groups: HashMap::new(),
+            finder: FinderConfigs::default(),
+            auth: GlobalAuthConfigs::default(),
+            vars: HashMap::new(),
+            hooks: HooksConfigs::default(),
+        }
+    }
 
     pub fn load_configs<P: AsRef<Path>>(path: Option<P>) -> Result<Self, ctx::CTXError> {
         let path = path
@@ -79,15 +1061,26 @@ impl Configs {
                 source: Some(e),
             })?;
 
-        c.try_deserialize()
+        c.clone()
+            .try_deserialize::<Self>()
+            // fallback to the legacy layout with `auth_commands`, then migrate it to `profiles`
+            .or_else(|e| {
+                c.try_deserialize::<LegacyConfigs>()
+                    .map(Self::from)
+                    .map_err(|_| e)
+            })
             .context(format!(
                 "failed to deserialize configurations from path: {}",
                 path.to_str().unwrap()
             ))
             .map_err(|e| ctx::CTXError::InvalidConfigurations {
-                message: "failed to deserialize configurations, check your configurations (~/.aws/configs.yaml)".to_string(),
+                message: format!(
+                    "failed to deserialize configurations ({}), check your configurations (~/.aws/configs.yaml)",
+                    e.root_cause()
+                ),
                 source: Some(e),
             })
+            .and_then(|c| c.validate().map(|_| c))
     }
 
     pub fn initialize_default_configs<P: AsRef<Path>>(
@@ -143,9 +1136,86 @@ mod tests {
 
     #[fixture]
     pub fn configs_text() -> String {
+        r#"profiles:
+  foo:
+    auth: |
+      echo 1
+    region: ap-northeast-1
+    tags: [prod]
+    env:
+      FOO: bar
+    protected: true
+    aliases: [f]
+    vars:
+      user: bob
+    hooks:
+      pre_use: echo foo
+      post_use: echo foo
+  baz:
+    auth:
+      script: echo baz
+      interpreter: bash
+      timeout: 10
+      output: credential_process
+      retries: 2
+      backoff: 3
+      non_retryable_exit_codes: [2]
+  qux:
+    auth:
+      assume_role:
+        source_profile: foo
+        role_arn: arn:aws:iam::123456789012:role/qux
+        session_name: "{{user}}"
+        duration: 900
+        external_id: ext
+        mfa_serial: arn:aws:iam::123456789012:mfa/bob
+  quux:
+    auth:
+      session_token:
+        source_profile: quux-long-term
+        mfa_serial: arn:aws:iam::123456789012:mfa/bob
+        duration: 43200
+  corge:
+    auth:
+      sso:
+        start_url: https://example.awsapps.com/start
+        region: us-east-1
+        account_id: "123456789012"
+        role_name: admin
+  __default:
+    auth: |
+      echo default
+groups:
+  prod:
+    profiles: [bar]
+    pattern: ^foo
+finder:
+  reverse: true
+  sort: recent
+  bindings:
+    copy: alt-c
+auth:
+  timeout: 60
+  strict: true
+  lock_timeout: 30
+  endpoints:
+    sts: http://localhost:8080
+    sso_oidc: http://localhost:8081
+    sso_portal: http://localhost:8082
+vars:
+  user: alice
+hooks:
+  post_use: echo global"#
+            .to_string()
+    }
+
+    #[fixture]
+    pub fn legacy_configs_text() -> String {
         r#"auth_commands:
   foo: |
-    echo 1"#
+    echo 1
+  __default: |
+    echo default"#
             .to_string()
     }
 
@@ -161,14 +1231,130 @@ mod tests {
     #[fixture]
     pub fn configs() -> Configs {
         Configs {
-            auth_commands: vec![("foo".to_string(), "echo 1".to_string())]
-                .into_iter()
-                .collect::<HashMap<String, String>>(),
+            profiles: hashmap! {
+                "foo".to_string() => ProfileConfigs {
+                    auth: Some("echo 1\n".to_string().into()),
+                    region: Some("ap-northeast-1".to_string()),
+                    tags: vec!["prod".to_string()],
+                    env: hashmap! {"FOO".to_string() => "bar".to_string()},
+                    protected: true,
+                    aliases: vec!["f".to_string()],
+                    vars: hashmap! {"user".to_string() => "bob".to_string()},
+                    hooks: HooksConfigs {
+                        pre_use: Some("echo foo".to_string()),
+                        post_use: Some("echo foo".to_string()),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                "baz".to_string() => ProfileConfigs {
+                    auth: Some(AuthConfigs::Options(AuthOptions {
+                        script: Some("echo baz".to_string()),
+                        interpreter: Some("bash".to_string()),
+                        timeout: Some(10),
+                        output: AuthOutput::CredentialProcess,
+                        retries: Some(2),
+                        backoff: Some(3),
+                        non_retryable_exit_codes: vec![2],
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                },
+                "qux".to_string() => ProfileConfigs {
+                    auth: Some(AuthConfigs::Options(AuthOptions {
+                        assume_role: Some(AssumeRoleConfigs {
+                            source_profile: "foo".to_string(),
+                            role_arn: "arn:aws:iam::123456789012:role/qux".to_string(),
+                            session_name: Some("{{user}}".to_string()),
+                            duration: Some(900),
+                            external_id: Some("ext".to_string()),
+                            mfa_serial: Some("arn:aws:iam::123456789012:mfa/bob".to_string()),
+                        }),
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                },
+                "quux".to_string() => ProfileConfigs {
+                    auth: Some(AuthConfigs::Options(AuthOptions {
+                        session_token: Some(SessionTokenConfigs {
+                            source_profile: "quux-long-term".to_string(),
+                            mfa_serial: "arn:aws:iam::123456789012:mfa/bob".to_string(),
+                            duration: Some(43200),
+                        }),
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                },
+                "corge".to_string() => ProfileConfigs {
+                    auth: Some(AuthConfigs::Options(AuthOptions {
+                        sso: Some(SsoConfigs {
+                            start_url: "https://example.awsapps.com/start".to_string(),
+                            region: "us-east-1".to_string(),
+                            account_id: "123456789012".to_string(),
+                            role_name: "admin".to_string(),
+                        }),
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                },
+                Configs::DEFAULT_AUTH_COMMAND_KEY.to_string() => ProfileConfigs {
+                    auth: Some("echo default\n".to_string().into()),
+                    ..Default::default()
+                },
+            },
+            groups: hashmap! {
+                "prod".to_string() => GroupConfigs {
+                    profiles: vec!["bar".to_string()],
+                    pattern: Some("^foo".to_string()),
+                },
+            },
+            finder: FinderConfigs {
+                reverse: true,
+                sort: ctx::SortOrder::Recent,
+                bindings: FinderBindings {
+                    copy: "alt-c".to_string(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            auth: GlobalAuthConfigs {
+                timeout: Some(60),
+                strict: true,
+                lock_timeout: Some(30),
+                endpoints: EndpointsConfigs {
+                    sts: Some("http://localhost:8080".to_string()),
+                    sso_oidc: Some("http://localhost:8081".to_string()),
+                    sso_portal: Some("http://localhost:8082".to_string()),
+                },
+            },
+            vars: hashmap! {"user".to_string() => "alice".to_string()},
+            hooks: HooksConfigs {
+                post_use: Some("echo global".to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[fixture]
+    pub fn legacy_configs() -> Configs {
+        Configs {
+            profiles: hashmap! {
+                "foo".to_string() => ProfileConfigs {
+                    auth: Some("echo 1\n".to_string().into()),
+                    ..Default::default()
+                },
+                Configs::DEFAULT_AUTH_COMMAND_KEY.to_string() => ProfileConfigs {
+                    auth: Some("echo default".to_string().into()),
+                    ..Default::default()
+                },
+            },
+            ..Configs::empty()
         }
     }
 
     #[rstest(input, expect)]
     #[case(configs_file(configs_text()), Ok(configs()))]
+    #[case(configs_file(legacy_configs_text()), Ok(legacy_configs()))]
     #[case(
         configs_file("invalid_yaml_format: a:a:".to_string()),
         Err(
@@ -182,7 +1368,180 @@ mod tests {
         configs_file("unknown_key: foo".to_string()),
         Err(
             ctx::CTXError::InvalidConfigurations {
-                message: "failed to deserialize configurations, check your configurations (~/.aws/configs.yaml)".to_string(),
+                message: "failed to deserialize configurations (unknown field `unknown_key`, expected one of `profiles`, `groups`, `finder`, `auth`, `vars`, `hooks`), check your configurations (~/.aws/configs.yaml)".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("finder: {sort: random}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "failed to deserialize configurations (unknown sort order (random), expected one of alphabetical, recent or frequent), check your configurations (~/.aws/configs.yaml)".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("profiles: {foo: {auth: {script: echo, output: json}}}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "failed to deserialize configurations (unknown output (json) of auth, expected one of inherit or credential_process), check your configurations (~/.aws/configs.yaml)".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("profiles: {foo: {auth: {script: echo, argv: [echo]}}}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "auth of profile (foo) must have one of `script`, `argv`, `assume_role`, `session_token` or `sso`".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("profiles: {foo: {auth: {argv: [echo], interpreter: bash}}}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "`interpreter` of profile (foo) cannot be used with `argv`".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("profiles: {foo: {auth: {assume_role: {source_profile: bar, role_arn: arn}, retries: 1}}}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "options of auth scripts of profile (foo) cannot be used with `assume_role`".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("profiles: {foo: {auth: {sso: {start_url: url, region: us-east-1, account_id: \"1\", role_name: admin}, output: credential_process}}}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "options of auth scripts of profile (foo) cannot be used with `sso`".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("profiles: {foo: {auth: {session_token: {source_profile: bar, mfa_serial: mfa}, assume_role: {source_profile: bar, role_arn: arn}}}}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "auth of profile (foo) must have one of `script`, `argv`, `assume_role`, `session_token` or `sso`".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("profiles: {foo: {auth: {session_token: {source_profile: foo, mfa_serial: mfa}}}}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "`source_profile` of profile (foo) must be another profile not to overwrite its long-term keys".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("profiles: {foo: {auth: {session_token: {source_profile: bar}}}}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "failed to deserialize configurations (missing field `mfa_serial`), check your configurations (~/.aws/configs.yaml)".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("finder: {height: 30}".to_string()),
+        Ok(Configs {
+            finder: FinderConfigs {
+                height: "30".to_string(),
+                ..Default::default()
+            },
+            ..Configs::empty()
+        })
+    )]
+    #[case(
+        configs_file("finder: {height: full}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "invalid height (full) of the finder, specify lines (e.g. 20) or a percentage (e.g. 30%)".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("finder: {preview_window: center}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "invalid preview window (center) of the finder, specify a position (up, down, left or right) and an optional size (e.g. right:50%)".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("finder: {color: 'light,fg:252,bg+:#1C1C1C'}".to_string()),
+        Ok(Configs {
+            finder: FinderConfigs {
+                color: Some("light,fg:252,bg+:#1C1C1C".to_string()),
+                ..Default::default()
+            },
+            ..Configs::empty()
+        })
+    )]
+    #[case(
+        configs_file("finder: {color: 'lihgt'}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "invalid color (lihgt) of the finder, specify a base scheme (e.g. light) and `name:color` separated by commas (e.g. light,fg:252,bg:#1c1c1c)".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("finder: {color: 'fg:256'}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "invalid color (fg:256) of the finder, specify a base scheme (e.g. light) and `name:color` separated by commas (e.g. light,fg:252,bg:#1c1c1c)".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("finder: {color: 'dark,foreground:1'}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "invalid color (dark,foreground:1) of the finder, specify a base scheme (e.g. light) and `name:color` separated by commas (e.g. light,fg:252,bg:#1c1c1c)".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("groups: {prod: {pattern: \"(\"}}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "invalid pattern of group (prod), check your configurations (~/.awsctx/configs.yaml)".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("finder: {bindings: {auth: ctrl-?}}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "invalid key (ctrl-?) for action (auth) of the finder".to_string(),
+                source: None
+            }
+        )
+    )]
+    #[case(
+        configs_file("finder: {bindings: {copy: ctrl-a}}".to_string()),
+        Err(
+            ctx::CTXError::InvalidConfigurations {
+                message: "key (ctrl-a) is bound to both actions (auth) and (copy) of the finder".to_string(),
                 source: None
             }
         )
@@ -210,6 +1569,191 @@ mod tests {
         }
     }
 
+    #[rstest(input, profile, expect)]
+    #[case(configs(), "foo", Some("echo 1\n"))]
+    #[case(configs(), "baz", Some("echo baz"))]
+    #[case(
+        configs(),
+        "qux",
+        Some("assume role (arn:aws:iam::123456789012:role/qux) with profile (foo)")
+    )]
+    #[case(
+        configs(),
+        "quux",
+        Some("get session token with profile (quux-long-term)")
+    )]
+    #[case(
+        configs(),
+        "corge",
+        Some("get role credentials of role (admin) in account (123456789012) by IAM Identity Center (https://example.awsapps.com/start)")
+    )]
+    #[case(
+        Configs {
+            profiles: hashmap! {"foo".to_string() => ProfileConfigs {
+                auth: Some(AuthConfigs::Options(AuthOptions {
+                    argv: vec!["aws".to_string(), "configure".to_string()],
+                    ..Default::default()
+                })),
+                ..Default::default()
+            }},
+            ..Configs::empty()
+        },
+        "foo",
+        Some("aws configure")
+    )]
+    // fallback to `__default` if auth is not configured for the profile
+    #[case(configs(), "bar", Some("echo default\n"))]
+    #[case(
+        Configs {
+            profiles: hashmap! {"foo".to_string() => ProfileConfigs::default()},
+            ..Configs::empty()
+        },
+        "foo",
+        None
+    )]
+    fn test_configs_get_auth_configs(input: Configs, profile: &str, expect: Option<&str>) {
+        let actual = input.get_auth_configs(profile).map(|a| a.command());
+        assert_eq!(expect.map(String::from), actual);
+    }
+
+    #[rstest(input, profile, expect)]
+    #[case(configs(), "foo", hashmap! {"user" => "bob"})]
+    #[case(configs(), "baz", hashmap! {"user" => "alice"})]
+    #[case(legacy_configs(), "foo", hashmap! {})]
+    fn test_configs_get_vars(input: Configs, profile: &str, expect: HashMap<&str, &str>) {
+        let actual = input.get_vars(profile);
+        let actual = actual
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect::<HashMap<&str, &str>>();
+        assert_eq!(expect, actual);
+    }
+
+    #[rstest(
+        profile,
+        attempt,
+        exit_code,
+        expect_retries,
+        expect_backoff,
+        expect_retryable
+    )]
+    #[case("baz", 1, Some(1), 2, Duration::from_secs(3), true)]
+    #[case("baz", 3, Some(2), 2, Duration::from_secs(12), false)]
+    // killed by a signal
+    #[case("baz", 1, None, 2, Duration::from_secs(3), true)]
+    #[case("foo", 2, Some(2), 0, Duration::from_secs(2), true)]
+    fn test_auth_configs_retry(
+        configs: Configs,
+        profile: &str,
+        attempt: u32,
+        exit_code: Option<i32>,
+        expect_retries: u32,
+        expect_backoff: Duration,
+        expect_retryable: bool,
+    ) {
+        let auth = configs.get_auth_configs(profile).unwrap();
+        assert_eq!(expect_retries, auth.retries());
+        assert_eq!(expect_backoff, auth.backoff(attempt));
+        assert_eq!(expect_retryable, auth.is_retryable(exit_code));
+    }
+
+    #[rstest(profile, hook, expect)]
+    #[case("foo", Hook::PreUse, vec!["echo foo"])]
+    #[case("foo", Hook::PostUse, vec!["echo global", "echo foo"])]
+    #[case("baz", Hook::PostUse, vec!["echo global"])]
+    #[case("foo", Hook::PreAuth, vec![])]
+    fn test_configs_get_hooks(configs: Configs, profile: &str, hook: Hook, expect: Vec<&str>) {
+        let actual = configs.get_hooks(profile, hook);
+        assert_eq!(expect, actual);
+    }
+
+    #[rstest(input, profile, expect)]
+    #[case(configs(), "baz", Some(Duration::from_secs(10)))]
+    // fallback to the global timeout if the profile has no timeout
+    #[case(configs(), "foo", Some(Duration::from_secs(60)))]
+    #[case(legacy_configs(), "foo", None)]
+    fn test_configs_get_auth_timeout(input: Configs, profile: &str, expect: Option<Duration>) {
+        let actual = input.get_auth_timeout(profile);
+        assert_eq!(expect, actual);
+    }
+
+    #[rstest(input, expect)]
+    #[case(configs(), Duration::from_secs(30))]
+    #[case(
+        legacy_configs(),
+        Duration::from_secs(Configs::DEFAULT_LOCK_TIMEOUT_SECS)
+    )]
+    fn test_configs_get_lock_timeout(input: Configs, expect: Duration) {
+        assert_eq!(expect, input.get_lock_timeout());
+    }
+
+    #[rstest(input, alias, expect)]
+    #[case(configs(), "f", Ok(Some("foo")))]
+    #[case(configs(), "unknown", Ok(None))]
+    #[case(
+        Configs {
+            profiles: hashmap! {
+                "foo".to_string() => ProfileConfigs { aliases: vec!["x".to_string()], ..Default::default() },
+                "bar".to_string() => ProfileConfigs { aliases: vec!["x".to_string()], ..Default::default() },
+            },
+            ..Configs::empty()
+        },
+        "x",
+        Err(ctx::CTXError::InvalidAlias {
+            alias: "x".to_string(),
+            message: "alias (x) is ambiguous, it is defined for profiles (bar, foo)".to_string(),
+            source: None,
+        })
+    )]
+    fn test_configs_resolve_alias(
+        input: Configs,
+        alias: &str,
+        expect: Result<Option<&str>, ctx::CTXError>,
+    ) {
+        let actual = input.resolve_alias(alias);
+        match (expect, actual) {
+            (Ok(expect), Ok(actual)) => assert_eq!(expect, actual),
+            (Err(expect), Err(actual)) => match (&expect, &actual) {
+                (
+                    ctx::CTXError::InvalidAlias {
+                        alias: expect_alias,
+                        message: expect_message,
+                        source: _,
+                    },
+                    ctx::CTXError::InvalidAlias {
+                        alias: actual_alias,
+                        message: actual_message,
+                        source: _,
+                    },
+                ) => {
+                    assert_eq!(expect_alias, actual_alias);
+                    assert_eq!(expect_message, actual_message);
+                }
+                _ => panic!("unexpected error: {}", actual),
+            },
+            _ => panic!("expect and actual are not match"),
+        }
+    }
+
+    #[rstest(profile, expect)]
+    #[case("foo", vec!["prod"])]
+    #[case("foobar", vec!["prod"])]
+    #[case("bar", vec!["prod"])]
+    #[case("baz", vec![])]
+    fn test_configs_groups_of(configs: Configs, profile: &str, expect: Vec<&str>) {
+        let actual = configs.groups_of(profile);
+        assert_eq!(expect, actual);
+    }
+
+    #[rstest]
+    fn test_configs_default() {
+        let actual = Configs::default();
+        assert!(actual
+            .get_auth_configs("unknown")
+            .is_some_and(|a| a.command().contains("aws configure --profile {{profile}}")));
+        assert!(Configs::empty().get_auth_configs("unknown").is_none());
+    }
+
     #[rstest]
     fn test_initialize_default_configs() {
         let tmpdir = TempDir::new().unwrap();
@@ -218,25 +1762,160 @@ mod tests {
         let expect = r#"# # Configurations for awsctx
 # # You can manually edit configurations according to the following usage
 
-# # To use subcommand `auth` or `refresh`, fill the below configs for each profile.
-# auth_commands:
+# # Configurations for each profile are placed under `profiles`.
+# profiles:
 #   # configuration for `foo` profile with aws configure
-#   foo: |
+#   foo:
+#     # To use subcommand `auth` or `refresh`, fill `auth` for the profile.
 #     # you can use pre-defined parameter `{{profile}}` which is replaced by key of this block
 #     # In this case, `{{profile}}` is replaced by `foo`
-#     aws configure --profile {{profile}}
+#     # `{{region}}`, `{{account_id}}`, `vars`, `{{env.NAME}}`, `{{user}}` (the OS user)
+#     # and `{{args}}` (arguments after `--` of `awsctx auth`) are also available
+#     auth: |
+#       aws configure --profile {{profile}}
 #   # configuration for `bar` profile with [onelogin-aws-cli](https://github.com/physera/onelogin-aws-cli)
-#   bar: |
-#     # In this case, name of one-login configuration is same as `profile`
-#     onelogin-aws-login -C {{profile}} --profile {{profile}} -u user@example.com
+#   bar:
+#     # In this case, the app id of one-login and the user are given by `vars`
+#     auth:
+#       script: |
+#         onelogin-aws-login -C {{app_id}} --profile {{profile}} -u {{username}} {{args}}
+#       # command to run the script with a path of the script, e.g. `bash` or `python3`,
+#       # `sh -c` is used by default, and a script starting with a shebang is executed directly
+#       interpreter: bash
+#       # seconds to wait for the script, the script is terminated after the timeout
+#       timeout: 300
+#       # times to rerun the script after failures, waiting `backoff` seconds (doubled for each retry)
+#       retries: 2
+#       backoff: 1
+#       # exit codes which are not retried, e.g. for a wrong password
+#       non_retryable_exit_codes: [2]
+#       # `inherit` (default) if the script writes credentials by itself, or
+#       # `credential_process` if the script prints credentials in the JSON format of credential_process
+#       # (https://docs.aws.amazon.com/cli/latest/userguide/cli-configure-sourcing-external.html),
+#       # then awsctx writes them to the profile
+#       output: inherit
+#     # optional attributes of the profile
+#     region: ap-northeast-1
+#     description: production account
+#     tags: [prod]
+#     # environment variables passed to the auth script
+#     env:
+#       ONELOGIN_REGION: us
+#     # ask to type the profile name or the account id before activating the profile
+#     account_id: "123456789012"
+#     protected: true
+#     aliases: [prod]
+#     # variables for the auth script, which override global `vars`
+#     vars:
+#       app_id: "123456"
+#     # hooks for the profile, which run after global `hooks`
+#     hooks:
+#       post_use: kubectl config use-context prod
+#   # configuration for `baz` profile with a command executed directly without a shell
+#   baz:
+#     auth:
+#       # each element is rendered separately, and `{{args}}` as an element is expanded to the arguments
+#       argv: [aws, sso, login, --profile, "{{profile}}", "{{args}}"]
+#   # configuration for `qux` profile assuming a role by awsctx itself without AWS CLI
+#   qux:
+#     auth:
+#       assume_role:
+#         # profile whose credentials are used, which may also assume a role to chain roles
+#         source_profile: foo
+#         role_arn: arn:aws:iam::123456789012:role/admin
+#         # template of the role session name, `awsctx-{{user}}` by default
+#         session_name: "awsctx-{{user}}"
+#         # optional seconds of the session and external id
+#         duration: 3600
+#         external_id: example
+#         # optional serial number of the MFA device, whose code is asked on auth
+#         mfa_serial: arn:aws:iam::123456789012:mfa/user
+#   # configuration for `quux` profile with MFA session credentials by long-term keys of `quux-long-term` profile
+#   quux:
+#     auth:
+#       session_token:
+#         source_profile: quux-long-term
+#         # serial number of the MFA device, whose code is asked on auth
+#         mfa_serial: arn:aws:iam::123456789012:mfa/user
+#         # optional seconds of the session
+#         duration: 43200
+#   # configuration for `corge` profile with role credentials by a login to IAM Identity Center (SSO).
+#   # The access token is cached in ~/.aws/sso/cache as AWS CLI does, and a login is asked when it expires
+#   corge:
+#     auth:
+#       sso:
+#         # URL of the AWS access portal and the region of IAM Identity Center
+#         start_url: https://my-sso-portal.awsapps.com/start
+#         region: us-east-1
+#         account_id: "123456789012"
+#         role_name: AdministratorAccess
 #   # default configuration for profiles without auth configuration
-#   __default: |
-#     aws configure --profile {{profile}}
-auth_commands:
-  __default: |
-    echo "This is default configuration for auth commands."
-    echo "You can edit this configuration on ~/.awsctx/configs.yaml according to your needs."
-    aws configure --profile {{profile}}
+#   __default:
+#     auth: |
+#       aws configure --profile {{profile}}
+# # Groups of profiles to narrow `list-contexts` and the interactive finder by `--group`
+# groups:
+#   prod:
+#     # profiles which belong to the group explicitly
+#     profiles: [bar]
+#     # regular expression to match profile names
+#     pattern: ^acme-prod-
+# # Auth configurations for all the profiles
+# auth:
+#   # seconds to wait for auth scripts, no timeout by default
+#   timeout: 600
+#   # fail on undefined variables in auth scripts instead of rendering empty strings
+#   strict: false
+#   # seconds to wait for auth of the same profile running in another terminal, 600 by default.
+#   # Its result is reused if it succeeds, and its lock is ignored after the timeout
+#   lock_timeout: 600
+#   # endpoints of AWS APIs called by awsctx, e.g. a local mock.
+#   # The regional endpoint for `region` of the profile (or the global one) is used by default
+#   endpoints:
+#     sts: https://sts.ap-northeast-1.amazonaws.com
+#     # endpoints of IAM Identity Center OIDC and the AWS access portal for `region` of `sso` by default
+#     sso_oidc: https://oidc.us-east-1.amazonaws.com
+#     sso_portal: https://portal.sso.us-east-1.amazonaws.com
+# # Variables for auth scripts of all the profiles
+# vars:
+#   username: user@example.com
+# # Scripts run before and after `use-context` (pre_use, post_use) and `auth` (pre_auth, post_auth),
+# # with `{{old_profile}}` and `{{new_profile}}` (also $AWSCTX_OLD_PROFILE and $AWSCTX_NEW_PROFILE).
+# # A failing `pre_*` hook aborts the operation
+# hooks:
+#   post_use: tmux refresh-client -S
+# # Configurations of the interactive finder
+# finder:
+#   # lines (e.g. 20) or a percentage of the terminal
+#   height: 30%
+#   # show the prompt at the top
+#   reverse: false
+#   prompt: "> "
+#   # color scheme in the format of `--color` option of skim
+#   color: dark
+#   preview: true
+#   # up, down, left or right with an optional size
+#   preview_window: right:50%
+#   # respect, ignore or smart
+#   case: smart
+#   # alphabetical, recent or frequent
+#   sort: alphabetical
+#   # keys of actions for the highlighted profile, `enter` switches to the profile
+#   bindings:
+#     auth: ctrl-a
+#     describe: ctrl-d
+#     delete: ctrl-x
+#     # print the profile name to stdout
+#     copy: ctrl-y
+#   # external finder reading profile names from stdin instead of the built-in finder,
+#   # the built-in finder falls back to a numbered menu if no terminal is available
+#   backend: fzf --multi
+profiles:
+  __default:
+    auth: |
+      echo "This is default configuration for auth commands."
+      echo "You can edit this configuration on ~/.awsctx/configs.yaml according to your needs."
+      aws configure --profile {{profile}}
 "#;
         let actual = fs::read_to_string(tmpfile).unwrap();
         assert_eq!(expect, actual);
@@ -1,23 +1,95 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
 use anyhow::Result;
-use skim::SkimOptions;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 pub trait CTX {
-    fn auth(&self, profile: &str) -> Result<Context, CTXError>;
+    /// Run the auth script of the profile with arguments, then make the profile active
+    fn auth(&self, profile: &str, args: &[String]) -> Result<Context, CTXError>;
+    /// Run the auth script of the profile without making it active
+    fn run_auth(&self, profile: &str, args: &[String]) -> Result<Context, CTXError>;
+    /// List profiles whose auth is configured, only in the group if specified
+    fn list_auth_profiles(&self, group: Option<&str>) -> Result<Vec<String>, CTXError>;
+    /// Run auth scripts of the profiles up to `jobs` at once without making them active,
+    /// scripts writing the credentials by themselves run one by one
+    fn run_auth_all(&self, profiles: &[String], args: &[String], jobs: usize) -> Vec<AuthResult>;
     fn list_contexts(&self) -> Result<Vec<Context>, CTXError>;
+    fn filter_contexts(&self, filter: &ContextFilter) -> Result<Vec<Context>, CTXError>;
     fn get_active_context(&self) -> Result<Context, CTXError>;
     fn use_context(&self, profile: &str) -> Result<Context, CTXError>;
-    fn use_context_interactive(&self, skim_options: SkimOptions) -> Result<Context, CTXError>;
+    fn describe_context(&self, profile: &str) -> Result<ContextDetails, CTXError>;
+    /// Describe the profiles at once, unknown profiles are skipped
+    fn describe_contexts(&self, profiles: &[String]) -> Result<Vec<ContextDetails>, CTXError>;
+    fn delete_context(&self, profile: &str) -> Result<Context, CTXError>;
+    fn exec(&self, profile: &str, command: &[String]) -> Result<i32, CTXError>;
 }
 
 #[derive(Error, Debug)]
 pub enum CTXError {
+    #[error("Auth script failed")]
+    AuthFailed {
+        profile: String,
+        /// How the script exited, e.g. `exit code 1`
+        status: String,
+        attempts: u32,
+        /// Last lines of stderr of the script
+        stderr: Vec<String>,
+        /// Log file of the output of the script
+        log: Option<PathBuf>,
+        source: Option<anyhow::Error>,
+    },
+    #[error("Auth request failed")]
+    AuthRequestFailed {
+        profile: String,
+        message: String,
+        source: Option<anyhow::Error>,
+    },
+    #[error("Auth script timed out")]
+    AuthTimeout {
+        profile: String,
+        timeout: Duration,
+        log: Option<PathBuf>,
+        source: Option<anyhow::Error>,
+    },
     #[error("Cannot read configuration")]
     CannotReadCredentials { source: Option<anyhow::Error> },
     #[error("Cannot write configuration")]
     CannotWriteCredentials { source: Option<anyhow::Error> },
+    #[error("Confirmation is required")]
+    ConfirmationRequired {
+        message: String,
+        source: Option<anyhow::Error>,
+    },
     #[error("Configuration is broken")]
     CredentialsIsBroken { source: Option<anyhow::Error> },
+    #[error("Input is required in non-interactive mode")]
+    InputRequired {
+        /// What needed the input
+        message: String,
+        source: Option<anyhow::Error>,
+    },
+    #[error("Hook failed")]
+    HookFailed {
+        hook: String,
+        profile: String,
+        source: Option<anyhow::Error>,
+    },
+    #[error("Invalid alias")]
+    InvalidAlias {
+        alias: String,
+        message: String,
+        source: Option<anyhow::Error>,
+    },
+    #[error("Invalid argument")]
+    InvalidArgument {
+        message: String,
+        source: Option<anyhow::Error>,
+    },
     #[error("Invalid configurations")]
     InvalidConfigurations {
         message: String,
@@ -32,6 +104,16 @@ pub enum CTXError {
     },
     #[error("No context is selected")]
     NoContextIsSelected { source: Option<anyhow::Error> },
+    #[error("Not confirmed")]
+    NotConfirmed {
+        profile: String,
+        source: Option<anyhow::Error>,
+    },
+    #[error("No such group")]
+    NoSuchGroup {
+        group: String,
+        source: Option<anyhow::Error>,
+    },
     #[error("No such profile")]
     NoSuchProfile {
         profile: String,
@@ -41,14 +123,100 @@ pub enum CTXError {
     UnexpectedError { source: Option<anyhow::Error> },
 }
 
-#[derive(Default, Debug, PartialEq, Eq)]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct Context {
     pub name: String,
     pub active: bool,
+    pub protected: bool,
+}
+
+/// Result of auth for one of profiles authenticated at once
+#[derive(Debug)]
+pub struct AuthResult {
+    pub profile: String,
+    pub result: Result<Context, CTXError>,
+    pub duration: Duration,
 }
 
-impl AsRef<str> for Context {
-    fn as_ref(&self) -> &str {
-        &self.name
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum KeyType {
+    /// Access keys of IAM users
+    LongTerm,
+    /// Access keys with a session token issued by STS
+    Temporary,
+    Unknown,
+}
+
+impl fmt::Display for KeyType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyType::LongTerm => write!(f, "long-term"),
+            KeyType::Temporary => write!(f, "temporary"),
+            KeyType::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct ContextDetails {
+    pub name: String,
+    pub active: bool,
+    pub aliases: Vec<String>,
+    pub groups: Vec<String>,
+    pub region: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub protected: bool,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub key_type: Option<KeyType>,
+    pub expiration: Option<DateTime<Utc>>,
+    pub auth_command: Option<String>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+#[derive(Default, Debug, PartialEq, Eq)]
+pub struct ContextFilter {
+    /// Name of a group defined in configs
+    pub group: Option<String>,
+    /// Regular expression to match profile names
+    pub pattern: Option<String>,
+    pub sort: SortOrder,
+}
+
+/// Order of contexts in `list-contexts` and the interactive finder
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", try_from = "String")]
+pub enum SortOrder {
+    /// Sort by profile names
+    #[default]
+    Alphabetical,
+    /// Most recently used profiles first
+    Recent,
+    /// Most frequently used profiles first
+    Frequent,
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alphabetical" => Ok(SortOrder::Alphabetical),
+            "recent" => Ok(SortOrder::Recent),
+            "frequent" => Ok(SortOrder::Frequent),
+            _ => Err(format!(
+                "unknown sort order ({}), expected one of alphabetical, recent or frequent",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for SortOrder {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
     }
 }
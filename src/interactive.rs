@@ -0,0 +1,126 @@
+use crate::configs::FinderAction;
+use crate::ctx::{self, CTX};
+use crate::selector::{ContextItem, SelectOptions, Selector};
+use crate::view::{format_context_details, show_context_details};
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+/// Result of an action which closes the interactive finder
+#[derive(Debug, PartialEq, Eq)]
+pub enum FinderOutcome {
+    /// The profile is made active
+    Switched(ctx::Context),
+    /// The profile is authenticated and made active
+    Authenticated(ctx::Context),
+    /// The profile name is requested to be printed
+    Copied(ctx::Context),
+}
+
+/// Build items of contexts with previews of their details
+fn context_items(ctx: &dyn CTX, contexts: &[ctx::Context]) -> Vec<ContextItem> {
+    let now = Utc::now();
+    let names = contexts.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+    // show empty previews if details cannot be loaded
+    let mut previews = ctx
+        .describe_contexts(&names)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|d| (d.name.clone(), format_context_details(&d, now).join("\n")))
+        .collect::<HashMap<_, _>>();
+    contexts
+        .iter()
+        .map(|c| ContextItem {
+            context: c.clone(),
+            preview: previews.remove(&c.name).unwrap_or_default(),
+        })
+        .collect()
+}
+
+fn header(filter: &ctx::ContextFilter) -> Option<String> {
+    filter.group.as_ref().map(|g| format!("group: {}", g))
+}
+
+/// Select a profile, then switch to it or run an action for it.
+/// The selector is opened again after describing or deleting a profile.
+pub fn use_context_interactive(
+    ctx: &dyn CTX,
+    selector: &dyn Selector,
+    filter: &ctx::ContextFilter,
+    query: Option<&str>,
+) -> Result<FinderOutcome, ctx::CTXError> {
+    // switch immediately if the query is an exact profile name or an alias
+    if let Some(query) = query {
+        match ctx.describe_context(query) {
+            Ok(details) => {
+                if ctx
+                    .filter_contexts(filter)?
+                    .iter()
+                    .any(|c| c.name == details.name)
+                {
+                    return ctx.use_context(&details.name).map(FinderOutcome::Switched);
+                }
+            }
+            Err(ctx::CTXError::NoSuchProfile { .. }) => (),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut options = SelectOptions {
+        query: query.map(String::from),
+        header: header(filter),
+        actions: true,
+        ..Default::default()
+    };
+    loop {
+        let contexts = ctx.filter_contexts(filter)?;
+        let selection = selector.select(context_items(ctx, &contexts), &options)?;
+        let context = selection
+            .names
+            .first()
+            .and_then(|name| contexts.into_iter().find(|c| &c.name == name))
+            .ok_or(ctx::CTXError::NoContextIsSelected { source: None })?;
+        match selection.action {
+            None => return ctx.use_context(&context.name).map(FinderOutcome::Switched),
+            Some(FinderAction::Auth) => {
+                return ctx
+                    .auth(&context.name, &[])
+                    .map(FinderOutcome::Authenticated)
+            }
+            Some(FinderAction::Copy) => return Ok(FinderOutcome::Copied(context)),
+            Some(FinderAction::Describe) => {
+                show_context_details(&ctx.describe_context(&context.name)?)
+            }
+            Some(FinderAction::Delete) => match ctx.delete_context(&context.name) {
+                Ok(context) => info!("<green>delete profile ({})</>", context.name),
+                Err(ctx::CTXError::NotConfirmed { .. }) => {
+                    warn!("profile ({}) is not deleted", context.name)
+                }
+                Err(e) => return Err(e),
+            },
+        }
+        // reopen the selector without the initial query
+        options.query = None;
+    }
+}
+
+/// Select profiles, multiple profiles can be selected if enabled
+pub fn select_contexts_interactive(
+    ctx: &dyn CTX,
+    selector: &dyn Selector,
+    filter: &ctx::ContextFilter,
+    multi: bool,
+) -> Result<Vec<ctx::Context>, ctx::CTXError> {
+    let contexts = ctx.filter_contexts(filter)?;
+    let options = SelectOptions {
+        multi,
+        header: header(filter),
+        ..Default::default()
+    };
+    let selection = selector.select(context_items(ctx, &contexts), &options)?;
+    Ok(contexts
+        .into_iter()
+        .filter(|c| selection.names.contains(&c.name))
+        .collect())
+}
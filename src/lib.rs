@@ -2,6 +2,15 @@ pub mod aws;
 pub mod configs;
 pub mod creds;
 pub mod ctx;
+pub mod interactive;
+pub mod lock;
+pub mod logs;
+pub mod process;
+pub mod prompt;
+pub mod selector;
+pub mod sso;
+pub mod state;
+pub mod sts;
 pub mod view;
 
 #[macro_use]
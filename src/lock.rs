@@ -0,0 +1,161 @@
+use dirs::home_dir;
+use std::fs::{self, File, TryLockError};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+
+pub static LOCK_DIR: Lazy<PathBuf> = Lazy::new(|| {
+    let mut path = home_dir().unwrap();
+    path.push(".awsctx/locks");
+    path
+});
+
+/// Interval to check whether a lock is released
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Lock of auth for a profile shared by awsctx processes.
+///
+/// The lock is released when it is dropped, or by the OS when the process crashes.
+#[derive(Debug)]
+pub struct AuthLock {
+    file: File,
+}
+
+/// Result of [`acquire`]
+#[derive(Debug)]
+pub enum Acquired {
+    /// No one else is authenticating the profile
+    Locked(AuthLock),
+    /// Auth of the profile which was in flight finished successfully while waiting
+    Finished,
+    /// The lock has been held longer than the timeout, so it is ignored
+    Stale,
+}
+
+impl AuthLock {
+    /// Record that auth finished successfully, so that processes waiting for the lock reuse it
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        self.file.write_all(Utc::now().to_rfc3339().as_bytes())?;
+        self.file.flush()
+    }
+}
+
+/// Escape characters of a profile name not allowed in file names, since profile names are free-form
+pub(crate) fn escape_file_name(profile: &str) -> String {
+    profile
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c.to_string(),
+            c => format!("%{:02X}", c as u32),
+        })
+        .collect()
+}
+
+fn lock_path(dir: &Path, profile: &str) -> PathBuf {
+    dir.join(format!("{}.lock", escape_file_name(profile)))
+}
+
+fn finished_at(file: &mut File) -> Option<DateTime<Utc>> {
+    let mut content = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut content).ok()?;
+    DateTime::parse_from_rfc3339(content.trim())
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Lock auth of the profile, waiting for auth in flight by others up to the timeout
+pub fn acquire(dir: &Path, profile: &str, timeout: Duration) -> io::Result<Acquired> {
+    fs::create_dir_all(dir)?;
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path(dir, profile))?;
+    let requested_at = Utc::now();
+    let started = Instant::now();
+    let mut waited = false;
+    loop {
+        match file.try_lock() {
+            Ok(()) => break,
+            Err(TryLockError::WouldBlock) if started.elapsed() >= timeout => {
+                return Ok(Acquired::Stale)
+            }
+            Err(TryLockError::WouldBlock) => {
+                if !waited {
+                    info!("wait for auth of profile ({}) in another process", profile);
+                    waited = true;
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+    }
+    if waited && finished_at(&mut file).is_some_and(|t| t >= requested_at) {
+        return Ok(Acquired::Finished);
+    }
+    Ok(Acquired::Locked(AuthLock { file }))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[rstest(profile, expect)]
+    #[case("foo", "foo.lock")]
+    #[case("foo/bar baz", "foo%2Fbar%20baz.lock")]
+    fn test_lock_path(profile: &str, expect: &str) {
+        let actual = lock_path(Path::new("/tmp"), profile);
+        assert_eq!(Path::new("/tmp").join(expect), actual);
+    }
+
+    #[rstest(finish, timeout, expect)]
+    // the result of auth in flight is reused
+    #[case(true, Duration::from_secs(10), "Finished")]
+    // the lock is taken over if auth in flight fails
+    #[case(false, Duration::from_secs(10), "Locked")]
+    // a lock held too long is ignored
+    #[case(true, Duration::from_millis(100), "Stale")]
+    fn test_acquire_waits_for_auth_in_flight(finish: bool, timeout: Duration, expect: &str) {
+        let dir = TempDir::new().unwrap();
+        let mut lock = match acquire(dir.path(), "foo", timeout).unwrap() {
+            Acquired::Locked(lock) => lock,
+            actual => panic!("unexpected result: {:?}", actual),
+        };
+        let waiter = thread::spawn({
+            let dir = dir.path().to_path_buf();
+            move || acquire(&dir, "foo", timeout).unwrap()
+        });
+        thread::sleep(Duration::from_millis(500));
+        if finish {
+            lock.finish().unwrap();
+        }
+        drop(lock);
+        let actual = waiter.join().unwrap();
+        assert!(format!("{:?}", actual).starts_with(expect), "{:?}", actual);
+    }
+
+    #[rstest]
+    fn test_acquire_does_not_reuse_auth_finished_before() {
+        let dir = TempDir::new().unwrap();
+        match acquire(dir.path(), "foo", Duration::from_secs(10)).unwrap() {
+            Acquired::Locked(mut lock) => lock.finish().unwrap(),
+            actual => panic!("unexpected result: {:?}", actual),
+        }
+        let actual = acquire(dir.path(), "foo", Duration::from_secs(10)).unwrap();
+        assert!(matches!(actual, Acquired::Locked(_)));
+        // locks of other profiles are independent
+        let actual = acquire(dir.path(), "bar", Duration::from_secs(10)).unwrap();
+        assert!(matches!(actual, Acquired::Locked(_)));
+    }
+}
@@ -3,14 +3,20 @@ use std::{io, path::PathBuf, rc::Rc};
 use awsctx::{
     aws::{AWS, CREDENTIALS_PATH},
     configs::Configs,
-    ctx::{CTXError, CTX},
-    view::{fatal_ctxerr, show_context, show_contexts},
+    ctx::{CTXError, ContextFilter, SortOrder, CTX},
+    interactive::{select_contexts_interactive, use_context_interactive, FinderOutcome},
+    lock::LOCK_DIR,
+    logs::LOG_DIR,
+    prompt::{is_ci, TerminalPrompter},
+    selector::default_selector,
+    sso::SSO_CACHE_DIR,
+    state::STATE_PATH,
+    view::{fatal_ctxerr, show_auth_results, show_context, show_context_details, show_contexts},
 };
 
 use clap::{IntoApp, Parser, Subcommand};
 use clap_complete::{generate, Generator, Shell};
 use simplelog as sl;
-use skim::prelude::SkimOptionsBuilder;
 
 #[derive(Parser)]
 #[clap(
@@ -22,6 +28,18 @@ use skim::prelude::SkimOptionsBuilder;
 struct Cli {
     #[clap(subcommand)]
     opts: Option<Opts>,
+    /// Query for the interactive finder, a profile name or an alias switches immediately
+    query: Option<String>,
+    /// Show only profiles in the group on the interactive finder
+    #[clap(long, short)]
+    group: Option<String>,
+    /// Skip confirmation for protected profiles
+    #[clap(long, short = 'y', global = true)]
+    yes: bool,
+    /// Never ask for input, operations requiring input fail with exit code 3.
+    /// This is enabled if the `CI` environment variable is `true`
+    #[clap(long, global = true)]
+    non_interactive: bool,
     /// Enable verbose output
     #[clap(long, short = 'v', parse(from_occurrences), global = true)]
     verbose: i8,
@@ -35,14 +53,94 @@ enum Opts {
     /// Auth awscli with the specified profile by pre-defined scripts, then make it active.
     ///
     /// This function requires the configuration set up for the specified profile before use.
+    /// With `--interactive`, `--all` or `--group`, the profiles are authenticated
+    /// without changing the active profile, up to `--jobs` profiles at once.
     #[clap(arg_required_else_help = true)]
     Auth {
+        #[clap(
+            long,
+            short,
+            help = "profile name",
+            required_unless_present_any = &["interactive", "all", "group"]
+        )]
+        profile: Option<String>,
+        #[clap(
+            long,
+            short,
+            conflicts_with_all = &["profile", "all"],
+            help = "select profiles by the interactive finder, <tab> selects multiple profiles"
+        )]
+        interactive: bool,
+        #[clap(
+            long,
+            short,
+            conflicts_with_all = &["profile", "group"],
+            help = "auth all the profiles whose auth is configured"
+        )]
+        all: bool,
+        #[clap(
+            long,
+            short,
+            conflicts_with = "profile",
+            help = "auth profiles in the group, or show only them with `--interactive`"
+        )]
+        group: Option<String>,
+        #[clap(
+            long,
+            short,
+            default_value = "1",
+            conflicts_with = "profile",
+            help = "number of auth scripts run at once, output of scripts is prefixed with profile names if more than 1"
+        )]
+        jobs: usize,
+        #[clap(
+            long,
+            conflicts_with = "profile",
+            help = "make the first profile authenticated successfully active"
+        )]
+        activate: bool,
+        #[clap(
+            last = true,
+            help = "arguments passed to the auth script as `{{args}}`"
+        )]
+        args: Vec<String>,
+    },
+    /// Show details of the specified profile.
+    #[clap(arg_required_else_help = true)]
+    Describe {
+        #[clap(long, short, help = "profile name")]
+        profile: String,
+    },
+    /// Execute a command with the specified profile.
+    ///
+    /// The command runs with `AWS_PROFILE` and environment variables configured for the profile.
+    #[clap(arg_required_else_help = true)]
+    Exec {
         #[clap(long, short, help = "profile name")]
         profile: String,
+        #[clap(required = true, last = true, help = "command to execute")]
+        command: Vec<String>,
     },
     /// List all the contexts in the credentials.
     #[clap(arg_required_else_help = false)]
-    ListContexts {},
+    ListContexts {
+        #[clap(long, short, help = "show only profiles in the group")]
+        group: Option<String>,
+        #[clap(
+            long,
+            short,
+            help = "show only profiles matching the regular expression"
+        )]
+        filter: Option<String>,
+        #[clap(
+            long,
+            short,
+            default_value = "alphabetical",
+            possible_values = ["alphabetical", "recent", "frequent"],
+            help = "order of profiles, the active profile comes first for recent and frequent"
+        )]
+        sort: SortOrder,
+    },
     /// Auth awscli for the active profile by pre-defined scripts
     ///
     /// This function requires the configuration set up for the specified profile before use.
@@ -89,31 +187,96 @@ fn main() {
     )
     .unwrap();
 
+    let non_interactive = cli.non_interactive || is_ci();
     let configs = Rc::new(fatal_ctxerr(
         Configs::initialize_default_configs::<PathBuf>(None),
     ));
-    let aws = AWS::new(Rc::clone(&configs), CREDENTIALS_PATH.clone()).unwrap();
+    let aws = AWS::new(Rc::clone(&configs), CREDENTIALS_PATH.clone())
+        .unwrap()
+        .with_prompter(Box::new(
+            TerminalPrompter::new(cli.yes).with_non_interactive(non_interactive),
+        ))
+        .with_state_path(STATE_PATH.clone())
+        .with_lock_dir(LOCK_DIR.clone())
+        .with_log_dir(LOG_DIR.clone())
+        .with_sso_cache_dir(SSO_CACHE_DIR.clone())
+        .with_non_interactive(non_interactive);
     let opts = cli.opts.unwrap_or(Opts::UseContextByInteractiveFinder {});
-    let skim_options = SkimOptionsBuilder::default()
-        .height(Some("30%"))
-        .multi(false)
-        .build()
-        .unwrap();
+    let selector = default_selector(&configs.finder, non_interactive);
 
     match opts {
         Opts::ActiveContext {} => {
             let context = fatal_ctxerr(aws.get_active_context());
             show_context(&context)
         }
-        Opts::Auth { profile } => {
-            let context = fatal_ctxerr(aws.auth(profile.as_str()));
+        Opts::Auth {
+            profile: Some(profile),
+            args,
+            ..
+        } => {
+            let context = fatal_ctxerr(aws.auth(profile.as_str(), &args));
             sl::info!(
                 "<green>successfully auth with profile ({}) and make it active</>",
                 context.name
             );
         }
-        Opts::ListContexts {} => {
-            let contexts = fatal_ctxerr(aws.list_contexts());
+        Opts::Auth {
+            profile: None,
+            interactive,
+            group,
+            jobs,
+            activate,
+            args,
+            ..
+        } => {
+            let profiles = if interactive {
+                let filter = ContextFilter {
+                    group: group.or(cli.group),
+                    sort: configs.finder.sort,
+                    ..Default::default()
+                };
+                match select_contexts_interactive(&aws, selector.as_ref(), &filter, true) {
+                    Ok(contexts) => contexts.into_iter().map(|c| c.name).collect(),
+                    Err(CTXError::NoContextIsSelected { source: _ }) => return,
+                    Err(err) => fatal_ctxerr(Err(err)),
+                }
+            } else {
+                fatal_ctxerr(aws.list_auth_profiles(group.as_deref()))
+            };
+            if profiles.is_empty() {
+                sl::warn!("no profiles to auth");
+                return;
+            }
+            let results = aws.run_auth_all(&profiles, &args, jobs);
+            show_auth_results(&results);
+            if activate {
+                if let Some(r) = results.iter().find(|r| r.result.is_ok()) {
+                    let context = fatal_ctxerr(aws.use_context(r.profile.as_str()));
+                    sl::info!("<green>switch to profile ({})</>", context.name);
+                }
+            }
+            if results.iter().any(|r| r.result.is_err()) {
+                std::process::exit(1);
+            }
+        }
+        Opts::Describe { profile } => {
+            let details = fatal_ctxerr(aws.describe_context(profile.as_str()));
+            show_context_details(&details)
+        }
+        Opts::Exec { profile, command } => {
+            let code = fatal_ctxerr(aws.exec(profile.as_str(), &command));
+            std::process::exit(code);
+        }
+        Opts::ListContexts {
+            group,
+            filter,
+            sort,
+        } => {
+            let contexts = fatal_ctxerr(aws.filter_contexts(&ContextFilter {
+                group,
+                pattern: filter,
+                sort,
+            }));
             show_contexts(&contexts)
         }
         Opts::UseContext { profile } => {
@@ -121,8 +284,20 @@ fn main() {
             sl::info!("<green>switch to profile ({})</>", context.name);
         }
         Opts::UseContextByInteractiveFinder {} => {
-            match aws.use_context_interactive(skim_options) {
-                Ok(context) => sl::info!("<green>switch to profile ({})</>", context.name),
+            let filter = ContextFilter {
+                group: cli.group,
+                sort: configs.finder.sort,
+                ..Default::default()
+            };
+            match use_context_interactive(&aws, selector.as_ref(), &filter, cli.query.as_deref()) {
+                Ok(FinderOutcome::Switched(context)) => {
+                    sl::info!("<green>switch to profile ({})</>", context.name)
+                }
+                Ok(FinderOutcome::Authenticated(context)) => sl::info!(
+                    "<green>successfully auth with profile ({}) and make it active</>",
+                    context.name
+                ),
+                Ok(FinderOutcome::Copied(context)) => println!("{}", context.name),
                 Err(err) => match err {
                     CTXError::NoContextIsSelected { source: _ } => (),
                     _ => fatal_ctxerr(Err(err)),
@@ -131,7 +306,7 @@ fn main() {
         }
         Opts::Refresh {} => {
             let active_context = fatal_ctxerr(aws.get_active_context());
-            fatal_ctxerr(aws.auth(active_context.name.as_str()));
+            fatal_ctxerr(aws.auth(active_context.name.as_str(), &[]));
             sl::info!(
                 "<green>successfully refresh credentials for profile ({})</>",
                 active_context.name
@@ -0,0 +1,550 @@
+use std::io::{self, IsTerminal, Read, Write};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Interval to check whether a child process exits
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Time given to a process group to exit after SIGTERM before SIGKILL
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(3);
+/// Lines of stderr kept to show on failures
+const STDERR_TAIL_LINES: usize = 10;
+const STDERR_KEPT_BYTES: usize = 64 * 1024;
+
+/// Process groups of running children to forward signals to
+static CHILD_GROUPS: Mutex<Vec<i32>> = Mutex::new(Vec::new());
+static INSTALL_HANDLERS: Once = Once::new();
+
+/// Exit of a command run by [`run`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Exit {
+    Exited(ExitStatus),
+    /// The command did not exit in time and its process group was terminated
+    TimedOut,
+}
+
+impl Exit {
+    pub fn success(&self) -> bool {
+        matches!(self, Exit::Exited(status) if status.success())
+    }
+}
+
+/// Describe how a command exited for error messages
+pub fn describe_status(status: &ExitStatus) -> String {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => format!("exit code {}", code),
+        (None, Some(signal)) => format!("signal {}", signal),
+        _ => "unknown status".to_string(),
+    }
+}
+
+/// Write end of the pipe to notify the forwarding thread of signals, -1 until it is created
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn notify_signal(signal: libc::c_int) {
+    // only async-signal-safe calls are allowed here, so the signal is forwarded by another thread
+    let signal = signal as u8;
+    unsafe {
+        libc::write(
+            SIGNAL_PIPE.load(Ordering::SeqCst),
+            &signal as *const u8 as _,
+            1,
+        )
+    };
+}
+
+fn forward_signal(signal: libc::c_int) {
+    let groups = CHILD_GROUPS.lock().unwrap_or_else(|e| e.into_inner());
+    for pgid in groups.iter() {
+        unsafe { libc::kill(-pgid, signal) };
+    }
+    if groups.is_empty() {
+        // behave as if no handler is installed when no command is running
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::kill(libc::getpid(), signal);
+        }
+    }
+}
+
+fn install_handlers() {
+    INSTALL_HANDLERS.call_once(|| {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            // signals are not forwarded, but commands can still run
+            return;
+        }
+        let [read_fd, write_fd] = fds;
+        // not to leak the pipe to commands
+        for fd in fds {
+            unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+        }
+        SIGNAL_PIPE.store(write_fd, Ordering::SeqCst);
+        thread::spawn(move || loop {
+            let mut signal = 0u8;
+            match unsafe { libc::read(read_fd, &mut signal as *mut u8 as _, 1) } {
+                1 => forward_signal(signal.into()),
+                -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => (),
+                _ => return,
+            }
+        });
+        unsafe {
+            let handler = notify_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            libc::signal(libc::SIGINT, handler);
+            libc::signal(libc::SIGTERM, handler);
+        }
+    });
+}
+
+/// A running process group registered to receive forwarded signals
+struct ChildGroup {
+    pgid: i32,
+    /// Whether the group is made the foreground of the terminal
+    foreground: bool,
+}
+
+impl ChildGroup {
+    fn register(pgid: i32, foreground: bool) -> Self {
+        CHILD_GROUPS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(pgid);
+        if foreground {
+            set_foreground(pgid);
+        }
+        ChildGroup { pgid, foreground }
+    }
+
+    fn kill(&self, signal: libc::c_int) {
+        unsafe { libc::kill(-self.pgid, signal) };
+    }
+}
+
+impl Drop for ChildGroup {
+    fn drop(&mut self) {
+        CHILD_GROUPS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|pgid| *pgid != self.pgid);
+        if self.foreground {
+            set_foreground(unsafe { libc::getpgrp() });
+        }
+    }
+}
+
+/// Whether this process owns the terminal, so that a child can take it over
+fn is_foreground() -> bool {
+    io::stdin().is_terminal() && unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp() }
+}
+
+fn set_foreground(pgid: i32) {
+    // a process not in the foreground is stopped by SIGTTOU unless it is ignored
+    unsafe {
+        let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+        libc::signal(libc::SIGTTOU, previous);
+    }
+}
+
+/// Run a command in a new process group and wait for it until the timeout.
+///
+/// The group is made the foreground of the terminal while it runs,
+/// so that Ctrl-C reaches the whole group. Otherwise SIGINT and SIGTERM are forwarded to it.
+/// When the timeout expires, the group is terminated by SIGTERM and then by SIGKILL.
+pub fn run(command: &mut Command, timeout: Option<Duration>) -> io::Result<Exit> {
+    let (mut child, group) = spawn(command, true)?;
+    wait(&mut child, &group, timeout)
+}
+
+/// Output of a command run by [`run_with_output`]
+#[derive(Debug)]
+pub struct Output {
+    pub exit: Exit,
+    /// Captured stdout, which is empty unless it is requested
+    pub stdout: Vec<u8>,
+    /// Last lines of stderr
+    pub stderr_tail: Vec<String>,
+}
+
+/// Receiver of the name of a stream (`stdout` or `stderr`) and each line of it
+pub type LineLog<'a> = &'a (dyn Fn(&str, &str) + Sync);
+
+/// Options of [`run_with_output`]
+#[derive(Default)]
+pub struct OutputOptions<'a> {
+    /// Capture stdout instead of inheriting it
+    pub capture_stdout: bool,
+    /// Prefix of each line of output. If set, the command runs in background without stdin,
+    /// so that commands can run in parallel
+    pub prefix: Option<&'a str>,
+    /// Called with each line of output
+    pub log: Option<LineLog<'a>>,
+}
+
+/// Same as [`run`], but stderr is shown while its last lines are kept
+pub fn run_with_output(
+    command: &mut Command,
+    timeout: Option<Duration>,
+    options: &OutputOptions,
+) -> io::Result<Output> {
+    let OutputOptions {
+        capture_stdout,
+        prefix,
+        log,
+    } = *options;
+    if capture_stdout || prefix.is_some() || log.is_some() {
+        command.stdout(Stdio::piped());
+    }
+    if prefix.is_some() {
+        command.stdin(Stdio::null());
+    }
+    let (mut child, group) = spawn(command.stderr(Stdio::piped()), prefix.is_none())?;
+    let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+    let join_error = |_| io::Error::other("failed to read output of the command");
+    // read in other threads not to block the command by a full pipe
+    let (exit, stdout, stderr) = thread::scope(|s| {
+        let log_line = |stream| {
+            move |line: &str| {
+                if let Some(log) = log {
+                    log(stream, line)
+                }
+            }
+        };
+        let stdout_reader = stdout.map(|mut stdout| {
+            s.spawn(move || {
+                if !capture_stdout {
+                    return tee(stdout, io::stdout(), prefix, &log_line("stdout")).map(|_| vec![]);
+                }
+                let mut output = vec![];
+                stdout.read_to_end(&mut output)?;
+                String::from_utf8_lossy(&output)
+                    .lines()
+                    .for_each(log_line("stdout"));
+                Ok(output)
+            })
+        });
+        let stderr_reader = stderr
+            .map(|stderr| s.spawn(move || tee(stderr, io::stderr(), prefix, &log_line("stderr"))));
+        let exit = wait(&mut child, &group, timeout);
+        let stdout = match stdout_reader {
+            Some(reader) => reader.join().map_err(join_error)?,
+            None => Ok(vec![]),
+        };
+        let stderr = match stderr_reader {
+            Some(reader) => reader.join().map_err(join_error)?,
+            None => Ok(vec![]),
+        };
+        io::Result::Ok((exit?, stdout?, stderr?))
+    })?;
+    let stderr = String::from_utf8_lossy(&stderr);
+    let lines = stderr.lines().collect::<Vec<_>>();
+    let stderr_tail = lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+    Ok(Output {
+        exit,
+        stdout,
+        stderr_tail,
+    })
+}
+
+/// Copy output of a command to `out` and return the last part of it.
+///
+/// Without a prefix, output is copied as soon as it is read, so that prompts without a newline are shown.
+/// With a prefix, output is copied line by line not to mix lines of commands running in parallel.
+/// Each line is also given to `log`.
+fn tee(
+    mut input: impl Read,
+    mut out: impl Write,
+    prefix: Option<&str>,
+    log: &dyn Fn(&str),
+) -> io::Result<Vec<u8>> {
+    let mut buf = [0; 4096];
+    let mut kept = vec![];
+    let mut line = vec![];
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        // ignore errors of writing to the terminal
+        if prefix.is_none() {
+            let _ = out.write_all(&buf[..n]).and_then(|_| out.flush());
+        }
+        line.extend_from_slice(&buf[..n]);
+        // the last line without a newline is handled at the end
+        if n == 0 && !line.is_empty() && !line.ends_with(b"\n") {
+            line.push(b'\n');
+        }
+        let end = line.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+        let lines = line.drain(..end).collect::<Vec<_>>();
+        if let Some(prefix) = prefix.filter(|_| !lines.is_empty()) {
+            let prefixed = lines
+                .split_inclusive(|b| *b == b'\n')
+                .flat_map(|l| prefix.as_bytes().iter().chain(l))
+                .copied()
+                .collect::<Vec<_>>();
+            let _ = out.write_all(&prefixed).and_then(|_| out.flush());
+        }
+        String::from_utf8_lossy(&lines).lines().for_each(log);
+        if n == 0 {
+            return Ok(kept);
+        }
+        kept.extend_from_slice(&buf[..n]);
+        if kept.len() > STDERR_KEPT_BYTES {
+            kept.drain(..kept.len() - STDERR_KEPT_BYTES);
+        }
+    }
+}
+
+fn spawn(command: &mut Command, foreground: bool) -> io::Result<(Child, ChildGroup)> {
+    install_handlers();
+    let foreground = foreground && is_foreground();
+    command.process_group(0);
+    if foreground {
+        unsafe {
+            // take over the terminal also in the child to avoid reading before the parent does it
+            command.pre_exec(|| {
+                set_foreground(libc::getpid());
+                Ok(())
+            });
+        }
+    }
+    let child = command.spawn()?;
+    let group = ChildGroup::register(child.id() as i32, foreground);
+    Ok((child, group))
+}
+
+fn wait(child: &mut Child, group: &ChildGroup, timeout: Option<Duration>) -> io::Result<Exit> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return child.wait().map(Exit::Exited),
+    };
+    if let Some(status) = wait_until(child, Instant::now() + timeout)? {
+        return Ok(Exit::Exited(status));
+    }
+    group.kill(libc::SIGTERM);
+    if wait_until(child, Instant::now() + KILL_GRACE_PERIOD)?.is_none() {
+        group.kill(libc::SIGKILL);
+        child.wait()?;
+    }
+    Ok(Exit::TimedOut)
+}
+
+fn wait_until(child: &mut Child, deadline: Instant) -> io::Result<Option<ExitStatus>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest(script, timeout, expect_success, expect_timed_out)]
+    #[case("exit 0", None, true, false)]
+    #[case("exit 3", None, false, false)]
+    #[case("exit 0", Some(Duration::from_secs(5)), true, false)]
+    #[case("sleep 5", Some(Duration::from_millis(200)), false, true)]
+    // SIGTERM is ignored by the script, so it is killed after the grace period
+    #[case(
+        "trap '' TERM; sleep 10",
+        Some(Duration::from_millis(200)),
+        false,
+        true
+    )]
+    fn test_run(
+        script: &str,
+        timeout: Option<Duration>,
+        expect_success: bool,
+        expect_timed_out: bool,
+    ) {
+        let started = Instant::now();
+        let actual = run(Command::new("sh").arg("-c").arg(script), timeout).unwrap();
+        assert_eq!(expect_success, actual.success());
+        assert_eq!(expect_timed_out, actual == Exit::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[rstest(
+        script,
+        timeout,
+        capture_stdout,
+        expect_timed_out,
+        expect_stdout,
+        expect_stderr
+    )]
+    #[case("echo foo; echo bar >&2", None, true, false, "foo\n", vec!["bar".to_string()])]
+    #[case("echo foo; echo bar >&2", None, false, false, "", vec!["bar".to_string()])]
+    // output until the timeout is captured
+    #[case(
+        "echo foo; echo bar >&2; sleep 5",
+        Some(Duration::from_millis(200)),
+        true,
+        true,
+        "foo\n",
+        vec!["bar".to_string()]
+    )]
+    // only the last lines of stderr are kept
+    #[case(
+        "seq 1 20 >&2",
+        None,
+        true,
+        false,
+        "",
+        (11..=20).map(|i| i.to_string()).collect::<Vec<_>>()
+    )]
+    fn test_run_with_output(
+        script: &str,
+        timeout: Option<Duration>,
+        capture_stdout: bool,
+        expect_timed_out: bool,
+        expect_stdout: &str,
+        expect_stderr: Vec<String>,
+    ) {
+        let logged = Mutex::new(vec![]);
+        let log =
+            |stream: &str, line: &str| logged.lock().unwrap().push(format!("{} {}", stream, line));
+        let actual = run_with_output(
+            Command::new("sh").arg("-c").arg(script),
+            timeout,
+            &OutputOptions {
+                capture_stdout,
+                log: Some(&log),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(expect_timed_out, actual.exit == Exit::TimedOut);
+        assert_eq!(expect_stdout, String::from_utf8(actual.stdout).unwrap());
+        assert_eq!(expect_stderr, actual.stderr_tail);
+        // lines are logged whether stdout is captured or not
+        let logged = logged.into_inner().unwrap();
+        assert_eq!(
+            script.contains("echo foo"),
+            logged.contains(&"stdout foo".to_string())
+        );
+        assert!(expect_stderr
+            .iter()
+            .all(|l| logged.contains(&format!("stderr {}", l))));
+    }
+
+    // run by test_run_with_output_not_piped in a child process whose stdout and stderr are a file
+    #[test]
+    #[ignore]
+    fn run_with_output_in_child() {
+        let logged = Mutex::new(vec![]);
+        let log =
+            |stream: &str, line: &str| logged.lock().unwrap().push(format!("{} {}", stream, line));
+        run_with_output(
+            Command::new("sh").arg("-c").arg("echo foo; echo bar >&2"),
+            None,
+            &OutputOptions {
+                log: Some(&log),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            vec!["stdout foo".to_string(), "stderr bar".to_string()],
+            logged.into_inner().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_run_with_output_not_piped() {
+        let output = tempfile::NamedTempFile::new().unwrap();
+        let status = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--ignored",
+                "--exact",
+                "--nocapture",
+                "process::tests::run_with_output_in_child",
+            ])
+            .stdout(output.reopen().unwrap())
+            .stderr(output.reopen().unwrap())
+            .status()
+            .unwrap();
+        let output = std::fs::read_to_string(output.path()).unwrap();
+        assert!(status.success(), "{}", output);
+        // output is still shown as well as logged
+        assert!(output.contains("foo\n"), "{}", output);
+        assert!(output.contains("bar\n"), "{}", output);
+        assert!(output.contains("1 passed"), "{}", output);
+    }
+
+    #[rstest(input, prefix, expect)]
+    #[case("foo\nbar", None, "foo\nbar")]
+    #[case("foo\nbar", Some("[baz] "), "[baz] foo\n[baz] bar\n")]
+    #[case("", Some("[baz] "), "")]
+    fn test_tee(input: &str, prefix: Option<&str>, expect: &str) {
+        let mut out = vec![];
+        let logged = Mutex::new(vec![]);
+        let kept = tee(input.as_bytes(), &mut out, prefix, &|l| {
+            logged.lock().unwrap().push(l.to_string())
+        })
+        .unwrap();
+        assert_eq!(expect, String::from_utf8(out).unwrap());
+        assert_eq!(input.as_bytes(), kept);
+        assert_eq!(
+            input.lines().collect::<Vec<_>>(),
+            logged.into_inner().unwrap()
+        );
+    }
+
+    #[rstest]
+    fn test_register_many_groups() {
+        let registered = |pgid| CHILD_GROUPS.lock().unwrap().contains(&pgid);
+        let mut children = (0..100)
+            .map(|_| spawn(Command::new("sleep").arg("10"), false).unwrap())
+            .collect::<Vec<_>>();
+        // every group receives forwarded signals however many commands run
+        assert!(children.iter().all(|(_, group)| registered(group.pgid)));
+        let pgids = children
+            .drain(..)
+            .map(|(mut child, group)| {
+                group.kill(libc::SIGKILL);
+                child.wait().unwrap();
+                group.pgid
+            })
+            .collect::<Vec<_>>();
+        assert!(pgids.into_iter().all(|pgid| !registered(pgid)));
+    }
+
+    #[rstest]
+    fn test_run_terminates_process_group() {
+        let pid_file = tempfile::NamedTempFile::new().unwrap();
+        let script = format!("sleep 10 & echo $! > {}; wait", pid_file.path().display());
+        let actual = run(
+            Command::new("sh").arg("-c").arg(script),
+            Some(Duration::from_millis(500)),
+        )
+        .unwrap();
+        assert_eq!(Exit::TimedOut, actual);
+        let pid: i32 = std::fs::read_to_string(pid_file.path())
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        // the background process of the script is also terminated
+        thread::sleep(Duration::from_millis(100));
+        let state = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+        assert!(state.is_empty() || state.contains(") Z "));
+    }
+}
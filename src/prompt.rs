@@ -0,0 +1,134 @@
+use crate::ctx;
+
+use std::env;
+use std::fmt::Debug;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::Mutex;
+
+use anyhow::Context;
+
+/// Held while a prompt waits for input
+static PROMPT_LOCK: Mutex<()> = Mutex::new(());
+
+pub trait Prompter: Debug {
+    /// Ask a user to type one of the answers, returns `false` if the input matches none of them
+    fn confirm(&self, message: &str, answers: &[String]) -> Result<bool, ctx::CTXError>;
+    /// Ask a user to type a value such as an MFA code
+    fn input(&self, message: &str) -> Result<String, ctx::CTXError>;
+    /// Copy the prompter with the same settings to be used in another thread
+    fn clone_for_thread(&self) -> Box<dyn Prompter + Send>;
+}
+
+/// Test whether awsctx runs in CI, where no one can answer prompts
+pub fn is_ci() -> bool {
+    env::var("CI").map(|v| is_truthy(&v)).unwrap_or_default()
+}
+
+fn is_truthy(value: &str) -> bool {
+    matches!(value.to_lowercase().as_str(), "true" | "1" | "yes")
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct TerminalPrompter {
+    assume_yes: bool,
+    non_interactive: bool,
+}
+
+impl TerminalPrompter {
+    pub fn new(assume_yes: bool) -> Self {
+        Self {
+            assume_yes,
+            ..Default::default()
+        }
+    }
+
+    /// Fail instead of asking for input unless `assume_yes` is set
+    pub fn with_non_interactive(mut self, non_interactive: bool) -> Self {
+        self.non_interactive = non_interactive;
+        self
+    }
+}
+
+impl Prompter for TerminalPrompter {
+    fn confirm(&self, message: &str, answers: &[String]) -> Result<bool, ctx::CTXError> {
+        if self.assume_yes {
+            return Ok(true);
+        }
+        if self.non_interactive {
+            return Err(ctx::CTXError::InputRequired {
+                message: format!("confirmation ({})", message),
+                source: None,
+            });
+        }
+        if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+            return Err(ctx::CTXError::ConfirmationRequired {
+                message: message.to_string(),
+                source: None,
+            });
+        }
+        let _lock = PROMPT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        warn!("<red>{}</>", message);
+        let input = read_line()?;
+        Ok(answers.iter().any(|a| a == input.trim()))
+    }
+
+    fn input(&self, message: &str) -> Result<String, ctx::CTXError> {
+        if self.non_interactive || !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+            return Err(ctx::CTXError::InputRequired {
+                message: message.to_string(),
+                source: None,
+            });
+        }
+        // not to mix prompts of profiles authenticated in parallel
+        let _lock = PROMPT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        info!("{}", message);
+        Ok(read_line()?.trim().to_string())
+    }
+
+    fn clone_for_thread(&self) -> Box<dyn Prompter + Send> {
+        Box::new(self.clone())
+    }
+}
+
+fn read_line() -> Result<String, ctx::CTXError> {
+    eprint!("> ");
+    let mut input = String::new();
+    io::stderr()
+        .flush()
+        .and_then(|_| io::stdin().lock().read_line(&mut input))
+        .context("failed to read an input")
+        .map_err(|e| ctx::CTXError::UnexpectedError { source: Some(e) })?;
+    Ok(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest(input, expect)]
+    #[case("true", true)]
+    #[case("True", true)]
+    #[case("1", true)]
+    #[case("false", false)]
+    #[case("", false)]
+    fn test_is_truthy(input: &str, expect: bool) {
+        assert_eq!(expect, is_truthy(input));
+    }
+
+    #[rstest(assume_yes, expect_ok)]
+    #[case(true, true)]
+    #[case(false, false)]
+    fn test_terminal_prompter_non_interactive(assume_yes: bool, expect_ok: bool) {
+        let prompter = TerminalPrompter::new(assume_yes).with_non_interactive(true);
+        let actual = prompter.confirm("profile (foo) is protected", &["foo".to_string()]);
+        match (expect_ok, actual) {
+            (true, Ok(true)) => (),
+            (false, Err(ctx::CTXError::InputRequired { message, .. })) => {
+                assert_eq!("confirmation (profile (foo) is protected)", message)
+            }
+            (_, actual) => panic!("unexpected result: {:?}", actual),
+        }
+    }
+}
@@ -0,0 +1,518 @@
+use crate::configs::{FinderAction, FinderCase, FinderConfigs};
+use crate::ctx;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use skim::prelude::{unbounded, Cow, SkimOptionsBuilder};
+use skim::{
+    AnsiString, CaseMatching, DisplayContext, ItemPreview, PreviewContext, Skim, SkimItem,
+    SkimItemReceiver, SkimItemSender,
+};
+use tuikit::key::from_keyname;
+
+/// Item to be selected with a preview of the context
+pub struct ContextItem {
+    pub context: ctx::Context,
+    pub preview: String,
+}
+
+impl SkimItem for ContextItem {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.context.name)
+    }
+
+    fn display<'a>(&'a self, context: DisplayContext<'a>) -> AnsiString<'a> {
+        if !self.context.protected {
+            return AnsiString::from(context);
+        }
+        // show protected profiles in red, keeping highlights of matched characters
+        let highlights = AnsiString::from(context)
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, attr))| *attr != Default::default())
+            .map(|(i, (_, attr))| (attr, (i as u32, i as u32 + 1)))
+            .collect();
+        let mut display = AnsiString::parse(&format!("\x1b[31m{}\x1b[0m", self.context.name));
+        display.override_attrs(highlights);
+        display
+    }
+
+    fn preview(&self, _context: PreviewContext) -> ItemPreview {
+        ItemPreview::AnsiText(self.preview.to_string())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SelectOptions {
+    pub multi: bool,
+    /// Initial query, a single match is selected immediately
+    pub query: Option<String>,
+    pub header: Option<String>,
+    /// Accept keys of actions besides `enter`
+    pub actions: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub names: Vec<String>,
+    /// Action requested for the selection, `None` means the default action
+    pub action: Option<FinderAction>,
+}
+
+/// Interface to select contexts from items ordered by priority
+pub trait Selector {
+    /// Returns `NoContextIsSelected` if the selection is cancelled
+    fn select(
+        &self,
+        items: Vec<ContextItem>,
+        options: &SelectOptions,
+    ) -> Result<Selection, ctx::CTXError>;
+}
+
+/// Build a selector according to configs and the environment,
+/// no selector is available in non-interactive mode
+pub fn default_selector(configs: &FinderConfigs, non_interactive: bool) -> Box<dyn Selector> {
+    if non_interactive {
+        return Box::new(NonInteractiveSelector);
+    }
+    match &configs.backend {
+        Some(command) => Box::new(CommandSelector {
+            command: command.to_string(),
+        }),
+        None if !is_available() => Box::new(MenuSelector),
+        None => Box::new(SkimSelector {
+            configs: configs.clone(),
+        }),
+    }
+}
+
+/// Built-in fuzzy finder
+pub struct SkimSelector {
+    pub configs: FinderConfigs,
+}
+
+impl Selector for SkimSelector {
+    fn select(
+        &self,
+        mut items: Vec<ContextItem>,
+        options: &SelectOptions,
+    ) -> Result<Selection, ctx::CTXError> {
+        let c = &self.configs;
+        let expect = options.actions.then(|| {
+            c.bindings
+                .keys()
+                .into_iter()
+                .map(|(_, key)| key)
+                .collect::<Vec<_>>()
+                .join(",")
+        });
+        let skim_options = SkimOptionsBuilder::default()
+            .height(Some(c.height.as_str()))
+            .layout(if c.reverse { "reverse" } else { "default" })
+            .prompt(Some(c.prompt.as_str()))
+            .color(c.color.as_deref())
+            .case(match c.case {
+                FinderCase::Respect => CaseMatching::Respect,
+                FinderCase::Ignore => CaseMatching::Ignore,
+                FinderCase::Smart => CaseMatching::Smart,
+            })
+            .multi(options.multi)
+            .header(options.header.as_deref())
+            .query(options.query.as_deref())
+            .select1(options.query.is_some())
+            .expect(expect)
+            // the preview is rendered by each item, so the command is not used
+            .preview(c.preview.then_some(""))
+            .preview_window(Some(c.preview_window.as_str()))
+            .build()
+            .map_err(|e| ctx::CTXError::UnexpectedError {
+                source: Some(anyhow!("failed to build options of skim: {}", e)),
+            })?;
+
+        // the first item is placed at the cursor, so alphabetical order after the active profile
+        // is reversed to be shown from top to bottom unless the prompt is at the top
+        if c.sort == ctx::SortOrder::Alphabetical && !c.reverse {
+            let active = items.first().map_or(0, |i| usize::from(i.context.active));
+            items[active..].reverse();
+        }
+        let (tx_item, rx_item): (SkimItemSender, SkimItemReceiver) = unbounded();
+        for item in items {
+            tx_item
+                .send(Arc::new(item))
+                .context("failed to send an item to skim")
+                .map_err(|e| ctx::CTXError::UnexpectedError { source: Some(e) })?;
+        }
+        drop(tx_item);
+
+        let out = Skim::run_with(&skim_options, Some(rx_item))
+            .filter(|out| !out.is_abort)
+            .ok_or(ctx::CTXError::NoContextIsSelected { source: None })?;
+        let names = out
+            .selected_items
+            .iter()
+            .map(|item| {
+                (*item)
+                    .as_any()
+                    .downcast_ref::<ContextItem>()
+                    .map(|item| item.context.name.to_string())
+                    .ok_or(ctx::CTXError::UnexpectedError {
+                        source: Some(anyhow!("unexpected error")),
+                    })
+            })
+            .collect::<Result<Vec<String>, ctx::CTXError>>()?;
+        if names.is_empty() {
+            return Err(ctx::CTXError::NoContextIsSelected { source: None });
+        }
+        let action = c
+            .bindings
+            .keys()
+            .into_iter()
+            .find(|(_, key)| from_keyname(key).as_ref() == Some(&out.final_key))
+            .map(|(action, _)| action);
+        Ok(Selection { names, action })
+    }
+}
+
+/// Numbered menu on stderr for environments without a terminal
+pub struct MenuSelector;
+
+impl Selector for MenuSelector {
+    fn select(
+        &self,
+        items: Vec<ContextItem>,
+        options: &SelectOptions,
+    ) -> Result<Selection, ctx::CTXError> {
+        let contexts = filter_by_query(items, options);
+        let names = select_by_menu(
+            &contexts,
+            options.multi,
+            options.header.as_deref(),
+            io::stdin().lock(),
+            io::stderr(),
+        )?;
+        // piped inputs are not echoed, so break the line of the prompt
+        if !io::stdin().is_terminal() {
+            eprintln!();
+        }
+        Ok(Selection {
+            names,
+            action: None,
+        })
+    }
+}
+
+/// External finder such as `fzf`
+pub struct CommandSelector {
+    pub command: String,
+}
+
+impl Selector for CommandSelector {
+    fn select(
+        &self,
+        items: Vec<ContextItem>,
+        options: &SelectOptions,
+    ) -> Result<Selection, ctx::CTXError> {
+        let contexts = filter_by_query(items, options);
+        let names = select_by_command(&self.command, &contexts)?;
+        let names = if options.multi {
+            names
+        } else {
+            names.into_iter().take(1).collect()
+        };
+        Ok(Selection {
+            names,
+            action: None,
+        })
+    }
+}
+
+/// Selector which always fails since no one can select profiles
+pub struct NonInteractiveSelector;
+
+impl Selector for NonInteractiveSelector {
+    fn select(
+        &self,
+        _items: Vec<ContextItem>,
+        _options: &SelectOptions,
+    ) -> Result<Selection, ctx::CTXError> {
+        Err(ctx::CTXError::InputRequired {
+            message: "selection of profiles by the interactive finder".to_string(),
+            source: None,
+        })
+    }
+}
+
+/// Selector returning pre-defined selections in order, which is useful for tests
+#[derive(Debug, Default)]
+pub struct ScriptedSelector {
+    selections: RefCell<VecDeque<Selection>>,
+}
+
+impl ScriptedSelector {
+    pub fn new(selections: Vec<Selection>) -> Self {
+        Self {
+            selections: RefCell::new(selections.into()),
+        }
+    }
+}
+
+impl Selector for ScriptedSelector {
+    fn select(
+        &self,
+        _items: Vec<ContextItem>,
+        _options: &SelectOptions,
+    ) -> Result<Selection, ctx::CTXError> {
+        self.selections
+            .borrow_mut()
+            .pop_front()
+            .ok_or(ctx::CTXError::NoContextIsSelected { source: None })
+    }
+}
+
+/// Narrow contexts by the initial query for selectors without fuzzy matching
+fn filter_by_query(items: Vec<ContextItem>, options: &SelectOptions) -> Vec<ctx::Context> {
+    items
+        .into_iter()
+        .map(|item| item.context)
+        .filter(|c| {
+            options
+                .query
+                .as_deref()
+                .map(|q| c.name.contains(q))
+                .unwrap_or(true)
+        })
+        .collect()
+}
+
+/// Test whether the interactive finder can run in this environment
+fn is_available() -> bool {
+    can_run_finder(
+        io::stdin().is_terminal(),
+        io::stdout().is_terminal(),
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .is_ok(),
+    )
+}
+
+/// The interactive finder requires terminals as stdin for key inputs and as stdout,
+/// and a controlling terminal for drawing
+fn can_run_finder(stdin_is_terminal: bool, stdout_is_terminal: bool, has_tty: bool) -> bool {
+    stdin_is_terminal && stdout_is_terminal && has_tty
+}
+
+/// Show contexts as a numbered menu, then read numbers of selected contexts
+pub fn select_by_menu<R: BufRead, W: Write>(
+    contexts: &[ctx::Context],
+    multi: bool,
+    header: Option<&str>,
+    mut input: R,
+    mut output: W,
+) -> Result<Vec<String>, ctx::CTXError> {
+    if contexts.is_empty() {
+        return Err(ctx::CTXError::NoContextIsSelected { source: None });
+    }
+    let write_err = |e: io::Error| ctx::CTXError::UnexpectedError {
+        source: Some(anyhow!("failed to write a menu: {}", e)),
+    };
+    if let Some(header) = header {
+        writeln!(output, "{}", header).map_err(write_err)?;
+    }
+    let width = contexts.len().to_string().len();
+    for (i, c) in contexts.iter().enumerate() {
+        let mark = if c.active { "*" } else { " " };
+        writeln!(
+            output,
+            "{} {:>width$}) {}",
+            mark,
+            i + 1,
+            c.name,
+            width = width
+        )
+        .map_err(write_err)?;
+    }
+    let prompt = if multi {
+        format!(
+            "select profiles [1-{}] separated by spaces: ",
+            contexts.len()
+        )
+    } else {
+        format!("select a profile [1-{}]: ", contexts.len())
+    };
+    loop {
+        write!(output, "{}", prompt)
+            .and_then(|_| output.flush())
+            .map_err(write_err)?;
+        let mut line = String::new();
+        let read = input
+            .read_line(&mut line)
+            .context("failed to read an input for the menu")
+            .map_err(|e| ctx::CTXError::UnexpectedError { source: Some(e) })?;
+        // EOF or an empty input cancels the selection
+        if read == 0 {
+            writeln!(output, "no input").map_err(write_err)?;
+            return Err(ctx::CTXError::NoContextIsSelected {
+                source: Some(anyhow!("no input")),
+            });
+        }
+        if line.trim().is_empty() {
+            return Err(ctx::CTXError::NoContextIsSelected { source: None });
+        }
+        let numbers = line
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=contexts.len()).contains(n))
+            })
+            .collect::<Option<Vec<usize>>>();
+        match numbers {
+            Some(numbers) if multi || numbers.len() == 1 => {
+                return Ok(numbers
+                    .into_iter()
+                    .map(|n| contexts[n - 1].name.to_string())
+                    .collect())
+            }
+            _ => writeln!(output, "invalid input: {}", line.trim()).map_err(write_err)?,
+        }
+    }
+}
+
+/// Run an external finder such as `fzf` with profile names on stdin,
+/// then read selected profile names from stdout
+pub fn select_by_command(
+    command: &str,
+    contexts: &[ctx::Context],
+) -> Result<Vec<String>, ctx::CTXError> {
+    let invalid = |e: anyhow::Error| ctx::CTXError::InvalidConfigurations {
+        message: format!("failed to run the finder backend ({})", command),
+        source: Some(e),
+    };
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| invalid(e.into()))?;
+    let names = contexts
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<Vec<&str>>()
+        .join("\n");
+    if let Some(mut stdin) = child.stdin.take() {
+        // the finder may exit without reading all the input
+        let _ = writeln!(stdin, "{}", names);
+    }
+    let output = child.wait_with_output().map_err(|e| invalid(e.into()))?;
+    match output.status.code() {
+        Some(0) => (),
+        // fzf, peco and sk exit with 1 for no match and 130 for interruption
+        Some(1) | Some(130) => return Err(ctx::CTXError::NoContextIsSelected { source: None }),
+        code => {
+            return Err(invalid(anyhow!(
+                "the finder backend exited with code {:?}",
+                code
+            )))
+        }
+    }
+    let selected = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| contexts.iter().any(|c| c.name == *l))
+        .map(String::from)
+        .collect::<Vec<String>>();
+    if selected.is_empty() {
+        return Err(ctx::CTXError::NoContextIsSelected { source: None });
+    }
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use rstest::*;
+
+    use super::*;
+
+    #[fixture]
+    fn contexts() -> Vec<ctx::Context> {
+        ["bar", "baz", "foo"]
+            .iter()
+            .map(|name| ctx::Context {
+                name: name.to_string(),
+                active: *name == "foo",
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[rstest(multi, input, expect)]
+    #[case(false, "2\n", Some(vec!["baz"]))]
+    #[case(true, "3 1\n", Some(vec!["foo", "bar"]))]
+    #[case(true, "1,2\n", Some(vec!["bar", "baz"]))]
+    // invalid inputs are asked again
+    #[case(false, "1 2\n4\nfoo\n3\n", Some(vec!["foo"]))]
+    #[case(false, "\n", None)]
+    #[case(false, "", None)]
+    fn test_select_by_menu(
+        contexts: Vec<ctx::Context>,
+        multi: bool,
+        input: &str,
+        expect: Option<Vec<&str>>,
+    ) {
+        let mut output = Vec::new();
+        let actual = select_by_menu(&contexts, multi, None, Cursor::new(input), &mut output);
+        match (expect, actual) {
+            (Some(expect), Ok(actual)) => assert_eq!(expect, actual),
+            (None, Err(ctx::CTXError::NoContextIsSelected { source: _ })) => (),
+            (_, actual) => panic!("unexpected result: {:?}", actual),
+        }
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("  1) bar\n  2) baz\n* 3) foo\n"));
+    }
+
+    #[rstest(stdin_is_terminal, stdout_is_terminal, has_tty, expect)]
+    #[case(true, true, true, true)]
+    #[case(false, true, true, false)]
+    #[case(true, false, true, false)]
+    #[case(true, true, false, false)]
+    fn test_can_run_finder(
+        stdin_is_terminal: bool,
+        stdout_is_terminal: bool,
+        has_tty: bool,
+        expect: bool,
+    ) {
+        assert_eq!(
+            expect,
+            can_run_finder(stdin_is_terminal, stdout_is_terminal, has_tty)
+        );
+    }
+
+    #[rstest(command, expect)]
+    #[case("grep z", Some(vec!["baz"]))]
+    #[case("cat", Some(vec!["bar", "baz", "foo"]))]
+    #[case("grep unknown", None)]
+    fn test_select_by_command(
+        contexts: Vec<ctx::Context>,
+        command: &str,
+        expect: Option<Vec<&str>>,
+    ) {
+        let actual = select_by_command(command, &contexts);
+        match (expect, actual) {
+            (Some(expect), Ok(actual)) => assert_eq!(expect, actual),
+            (None, Err(ctx::CTXError::NoContextIsSelected { source: _ })) => (),
+            (_, actual) => panic!("unexpected result: {:?}", actual),
+        }
+    }
+}
@@ -0,0 +1,461 @@
+use crate::creds::ProfileCredentials;
+
+use dirs::home_dir;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use sha1::{Digest, Sha1};
+
+/// Directory of access tokens shared with AWS CLI
+pub static SSO_CACHE_DIR: Lazy<PathBuf> = Lazy::new(|| {
+    let mut path = home_dir().unwrap();
+    path.push(".aws/sso/cache");
+    path
+});
+
+const CLIENT_NAME: &str = "awsctx";
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+/// Interval to poll the token, which is given by the device authorization usually
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 5;
+
+/// Client of IAM Identity Center (SSO) OIDC and the access portal
+#[derive(Debug, Clone)]
+pub struct Sso {
+    oidc_endpoint: String,
+    portal_endpoint: String,
+    timeout: Option<Duration>,
+}
+
+/// Access token cached in the format of AWS CLI, with the registered client to reuse it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Token {
+    pub start_url: String,
+    pub region: String,
+    pub access_token: String,
+    pub expires_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration_expires_at: Option<DateTime<Utc>>,
+}
+
+/// Client registered to IAM Identity Center OIDC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registration {
+    pub client_id: String,
+    pub client_secret: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Device authorization which a user confirms in a browser
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceAuthorization {
+    device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    #[serde(default)]
+    pub verification_uri_complete: Option<String>,
+    expires_in: u64,
+    #[serde(default)]
+    interval: Option<u64>,
+}
+
+impl Token {
+    pub fn is_valid(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at > now
+    }
+
+    /// The client registered on login, `None` if it is expired
+    pub fn registration(&self, now: DateTime<Utc>) -> Option<Registration> {
+        Some(Registration {
+            client_id: self.client_id.clone()?,
+            client_secret: self.client_secret.clone()?,
+            expires_at: self.registration_expires_at.filter(|e| *e > now)?,
+        })
+    }
+}
+
+/// Path of the cached token for the start URL, which is named by the SHA1 of the URL as AWS CLI does
+pub fn cache_path(dir: &Path, start_url: &str) -> PathBuf {
+    dir.join(format!(
+        "{}.json",
+        hex::encode(Sha1::digest(start_url.as_bytes()))
+    ))
+}
+
+/// Load the cached token for the start URL, `None` if it is not cached or broken
+pub fn load_token(dir: &Path, start_url: &str) -> Option<Token> {
+    let content = fs::read_to_string(cache_path(dir, start_url)).ok()?;
+    serde_json::from_str::<Token>(&content)
+        .ok()
+        .filter(|t| t.start_url == start_url)
+}
+
+/// Cache the token, which only the user can read.
+/// Fields of the cached file which awsctx does not know (e.g. `refreshToken` of AWS CLI) are kept,
+/// and the file is replaced at once not to be read half-written by AWS CLI
+pub fn save_token(dir: &Path, token: &Token) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = cache_path(dir, &token.start_url);
+    let mut cache = fs::read(&path)
+        .ok()
+        .and_then(|c| serde_json::from_slice::<serde_json::Map<_, _>>(&c).ok())
+        .unwrap_or_default();
+    if let serde_json::Value::Object(fields) = serde_json::to_value(token)? {
+        cache.extend(fields);
+    }
+    // temporary files are readable only by the user
+    let mut file = tempfile::Builder::new()
+        .prefix(".awsctx-")
+        .tempfile_in(dir)?;
+    file.write_all(serde_json::to_string_pretty(&cache)?.as_bytes())?;
+    file.persist(path)?;
+    Ok(())
+}
+
+/// Response of an API, which is either a JSON body or an error with the status and the body
+type Response = std::result::Result<serde_json::Value, (u16, serde_json::Value)>;
+
+impl Sso {
+    /// Client for the endpoints, the ones of the region are used if not set
+    pub fn new(oidc_endpoint: Option<&str>, portal_endpoint: Option<&str>, region: &str) -> Self {
+        let endpoint = |endpoint: Option<&str>, default: String| {
+            endpoint.map_or(default, |e| e.trim_end_matches('/').to_string())
+        };
+        Self {
+            oidc_endpoint: endpoint(
+                oidc_endpoint,
+                format!("https://oidc.{}.amazonaws.com", region),
+            ),
+            portal_endpoint: endpoint(
+                portal_endpoint,
+                format!("https://portal.sso.{}.amazonaws.com", region),
+            ),
+            timeout: None,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn register_client(&self) -> Result<Registration> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Output {
+            client_id: String,
+            client_secret: String,
+            client_secret_expires_at: i64,
+        }
+        let output: Output = self.oidc(
+            "client/register",
+            &json!({"clientName": CLIENT_NAME, "clientType": "public"}),
+        )?;
+        Ok(Registration {
+            client_id: output.client_id,
+            client_secret: output.client_secret,
+            expires_at: Utc
+                .timestamp_opt(output.client_secret_expires_at, 0)
+                .single()
+                .ok_or_else(|| anyhow!("invalid expiration of the client"))?,
+        })
+    }
+
+    pub fn start_device_authorization(
+        &self,
+        registration: &Registration,
+        start_url: &str,
+    ) -> Result<DeviceAuthorization> {
+        self.oidc(
+            "device_authorization",
+            &json!({
+                "clientId": registration.client_id,
+                "clientSecret": registration.client_secret,
+                "startUrl": start_url,
+            }),
+        )
+    }
+
+    /// Wait for a user to confirm the device authorization, returns the access token and its expiration
+    pub fn create_token(
+        &self,
+        registration: &Registration,
+        device: &DeviceAuthorization,
+    ) -> Result<(String, DateTime<Utc>)> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Output {
+            access_token: String,
+            expires_in: i64,
+        }
+        let body = json!({
+            "clientId": registration.client_id,
+            "clientSecret": registration.client_secret,
+            "grantType": DEVICE_CODE_GRANT_TYPE,
+            "deviceCode": device.device_code,
+        });
+        let deadline = Instant::now() + Duration::from_secs(device.expires_in);
+        let mut interval =
+            Duration::from_secs(device.interval.unwrap_or(DEFAULT_POLL_INTERVAL_SECS));
+        loop {
+            thread::sleep(interval);
+            let error = match self.send(self.post(&self.oidc_endpoint, "token"), Some(&body))? {
+                Ok(output) => {
+                    let output: Output = serde_json::from_value(output)
+                        .context("unexpected response of CreateToken")?;
+                    return Ok((
+                        output.access_token,
+                        Utc::now() + chrono::Duration::seconds(output.expires_in),
+                    ));
+                }
+                Err((status, body)) => match body["error"].as_str() {
+                    Some("authorization_pending") => None,
+                    // see https://datatracker.ietf.org/doc/html/rfc8628#section-3.5
+                    Some("slow_down") => {
+                        interval += Duration::from_secs(DEFAULT_POLL_INTERVAL_SECS);
+                        None
+                    }
+                    _ => Some(api_error("CreateToken", status, &body)),
+                },
+            };
+            if let Some(error) = error {
+                return Err(error);
+            }
+            if Instant::now() >= deadline {
+                return Err(anyhow!(
+                    "the device authorization is not confirmed in {} seconds",
+                    device.expires_in
+                ));
+            }
+        }
+    }
+
+    /// Get credentials of the role in the account, `None` if the access token is rejected
+    pub fn get_role_credentials(
+        &self,
+        access_token: &str,
+        account_id: &str,
+        role_name: &str,
+    ) -> Result<Option<ProfileCredentials>> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct RoleCredentials {
+            access_key_id: String,
+            secret_access_key: String,
+            session_token: String,
+            /// Milliseconds since the epoch
+            expiration: i64,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Output {
+            role_credentials: RoleCredentials,
+        }
+        let request = self
+            .agent()
+            .get(&format!("{}/federation/credentials", self.portal_endpoint))
+            .query("account_id", account_id)
+            .query("role_name", role_name)
+            .set("x-amz-sso_bearer_token", access_token);
+        let output: Output = match self.send(request, None)? {
+            Ok(output) => serde_json::from_value(output)
+                .context("unexpected response of GetRoleCredentials")?,
+            Err((401, _)) => return Ok(None),
+            Err((status, body)) => return Err(api_error("GetRoleCredentials", status, &body)),
+        };
+        let credentials = output.role_credentials;
+        Ok(Some(ProfileCredentials {
+            access_key_id: credentials.access_key_id,
+            secret_access_key: credentials.secret_access_key,
+            session_token: Some(credentials.session_token),
+            expiration: Some(
+                Utc.timestamp_millis_opt(credentials.expiration)
+                    .single()
+                    .ok_or_else(|| anyhow!("invalid expiration of the credentials"))?,
+            ),
+        }))
+    }
+
+    fn agent(&self) -> ureq::Agent {
+        let mut agent = ureq::AgentBuilder::new();
+        if let Some(timeout) = self.timeout {
+            agent = agent.timeout(timeout);
+        }
+        agent.build()
+    }
+
+    fn post(&self, endpoint: &str, path: &str) -> ureq::Request {
+        self.agent()
+            .post(&format!("{}/{}", endpoint, path))
+            .set("content-type", "application/json")
+    }
+
+    /// Call an API of OIDC, whose errors are returned as they are
+    fn oidc<T: DeserializeOwned>(&self, path: &str, body: &serde_json::Value) -> Result<T> {
+        match self.send(self.post(&self.oidc_endpoint, path), Some(body))? {
+            Ok(output) => serde_json::from_value(output)
+                .with_context(|| format!("unexpected response of {}", path)),
+            Err((status, body)) => Err(api_error(path, status, &body)),
+        }
+    }
+
+    fn send(&self, request: ureq::Request, body: Option<&serde_json::Value>) -> Result<Response> {
+        let url = request.url().to_string();
+        let response = match body {
+            Some(body) => request.send_string(&body.to_string()),
+            None => request.call(),
+        };
+        let parse = |response: ureq::Response| {
+            let text = response.into_string().unwrap_or_default();
+            serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text))
+        };
+        match response {
+            Ok(response) => Ok(Ok(parse(response))),
+            Err(ureq::Error::Status(status, response)) => Ok(Err((status, parse(response)))),
+            Err(e) => Err(anyhow!(
+                "failed to request IAM Identity Center ({}): {}",
+                url,
+                e
+            )),
+        }
+    }
+}
+
+/// Error of an API from the status and the body, which has `error` and `error_description` of OAuth
+/// or `message` of AWS
+fn api_error(api: &str, status: u16, body: &serde_json::Value) -> anyhow::Error {
+    let message = ["error_description", "message", "error"]
+        .iter()
+        .find_map(|k| body[k].as_str())
+        .map(String::from)
+        .unwrap_or_else(|| body.as_str().unwrap_or_default().trim().to_string());
+    anyhow!(
+        "{} of IAM Identity Center failed with status {}: {}",
+        api,
+        status,
+        message
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[rstest]
+    fn test_cache_path() {
+        let actual = cache_path(Path::new("/tmp"), "https://my-sso-portal.awsapps.com/start");
+        assert_eq!(
+            Path::new("/tmp/c7aaaf71fcc8777ae2475525ed049d39fe16c484.json"),
+            actual
+        );
+    }
+
+    #[rstest]
+    fn test_save_and_load_token() {
+        let dir = TempDir::new().unwrap();
+        let token = Token {
+            start_url: "https://example.awsapps.com/start".to_string(),
+            region: "us-east-1".to_string(),
+            access_token: "token".to_string(),
+            expires_at: Utc.with_ymd_and_hms(2099, 1, 1, 0, 0, 0).unwrap(),
+            client_id: None,
+            client_secret: None,
+            registration_expires_at: None,
+        };
+        save_token(dir.path(), &token).unwrap();
+        assert_eq!(
+            Some(token),
+            load_token(dir.path(), "https://example.awsapps.com/start")
+        );
+        assert_eq!(
+            None,
+            load_token(dir.path(), "https://other.awsapps.com/start")
+        );
+    }
+
+    #[rstest]
+    fn test_save_token_keeps_fields_of_aws_cli() {
+        let dir = TempDir::new().unwrap();
+        let start_url = "https://example.awsapps.com/start";
+        fs::write(
+            cache_path(dir.path(), start_url),
+            r#"{"startUrl": "https://example.awsapps.com/start", "region": "us-east-1", "accessToken": "old", "expiresAt": "2020-01-01T00:00:00Z", "refreshToken": "refresh", "clientId": "cli-client", "clientSecret": "cli-secret", "registrationExpiresAt": "2099-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        let token = Token {
+            start_url: start_url.to_string(),
+            region: "us-east-1".to_string(),
+            access_token: "new".to_string(),
+            expires_at: Utc.with_ymd_and_hms(2099, 1, 1, 0, 0, 0).unwrap(),
+            client_id: None,
+            client_secret: None,
+            registration_expires_at: None,
+        };
+        save_token(dir.path(), &token).unwrap();
+        let actual: serde_json::Value =
+            serde_json::from_slice(&fs::read(cache_path(dir.path(), start_url)).unwrap()).unwrap();
+        assert_eq!(
+            json!({
+                "startUrl": start_url,
+                "region": "us-east-1",
+                "accessToken": "new",
+                "expiresAt": "2099-01-01T00:00:00Z",
+                "refreshToken": "refresh",
+                "clientId": "cli-client",
+                "clientSecret": "cli-secret",
+                "registrationExpiresAt": "2099-01-01T00:00:00Z",
+            }),
+            actual
+        );
+        // only the cache file is left
+        assert_eq!(1, fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[rstest]
+    fn test_load_token_of_aws_cli() {
+        let dir = TempDir::new().unwrap();
+        let start_url = "https://example.awsapps.com/start";
+        fs::write(
+            cache_path(dir.path(), start_url),
+            r#"{"startUrl": "https://example.awsapps.com/start", "region": "us-east-1", "accessToken": "token", "expiresAt": "2020-01-01T00:00:00Z", "refreshToken": "refresh"}"#,
+        )
+        .unwrap();
+        let actual = load_token(dir.path(), start_url).unwrap();
+        assert_eq!("token", actual.access_token);
+        assert!(!actual.is_valid(Utc::now()));
+        assert_eq!(None, actual.registration(Utc::now()));
+    }
+
+    #[rstest(input, expect)]
+    #[case(
+        json!({"error": "invalid_grant", "error_description": "expired"}),
+        "token of IAM Identity Center failed with status 400: expired"
+    )]
+    #[case(
+        json!({"message": "bad request"}),
+        "token of IAM Identity Center failed with status 400: bad request"
+    )]
+    #[case(
+        json!("oops\n"),
+        "token of IAM Identity Center failed with status 400: oops"
+    )]
+    fn test_api_error(input: serde_json::Value, expect: &str) {
+        assert_eq!(expect, api_error("token", 400, &input).to_string());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::ctx;
+
+use dirs::home_dir;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+pub static STATE_PATH: Lazy<PathBuf> = Lazy::new(|| {
+    let mut path = home_dir().unwrap();
+    path.push(".awsctx/state.json");
+    path
+});
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProfileState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub use_count: u64,
+}
+
+/// Usage of profiles recorded by awsctx
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct State {
+    #[serde(default)]
+    pub profiles: HashMap<String, ProfileState>,
+}
+
+impl State {
+    /// Load a state file, returns an empty state if the file does not exist
+    pub fn load_state<P: AsRef<Path>>(path: P) -> Result<Self, ctx::CTXError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(path)
+            .context(format!(
+                "failed to read a state file: {}",
+                path.to_str().unwrap()
+            ))
+            .map_err(|e| ctx::CTXError::UnexpectedError { source: Some(e) })?;
+        serde_json::from_str(&contents)
+            .context(format!(
+                "failed to deserialize a state file: {}",
+                path.to_str().unwrap()
+            ))
+            .map_err(|e| ctx::CTXError::UnexpectedError { source: Some(e) })
+    }
+
+    pub fn dump_state<P: AsRef<Path>>(&self, path: P) -> Result<(), ctx::CTXError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context("failed to create a state directory")
+                .map_err(|e| ctx::CTXError::UnexpectedError { source: Some(e) })?;
+        }
+        let mut file = fs::File::create(path)
+            .context("failed to create a state file")
+            .map_err(|e| ctx::CTXError::UnexpectedError { source: Some(e) })?;
+        serde_json::to_writer_pretty(&mut file, self)
+            .context("failed to serialize a state")
+            .map_err(|e| ctx::CTXError::UnexpectedError { source: Some(e) })?;
+        file.flush()
+            .context("failed to flush a state file")
+            .map_err(|e| ctx::CTXError::UnexpectedError { source: Some(e) })
+    }
+
+    pub fn get_profile_state(&self, profile: &str) -> Option<&ProfileState> {
+        self.profiles.get(profile)
+    }
+
+    pub fn record_usage(&mut self, profile: &str, at: DateTime<Utc>) {
+        let state = self.profiles.entry(profile.to_string()).or_default();
+        state.last_used = Some(at);
+        state.use_count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use maplit::hashmap;
+    use rstest::*;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[fixture]
+    pub fn state() -> State {
+        State {
+            profiles: hashmap! {
+                "foo".to_string() => ProfileState {
+                    last_used: Some(Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap()),
+                    use_count: 3,
+                },
+            },
+        }
+    }
+
+    #[rstest]
+    fn test_state_load_state_without_file() {
+        let tmpdir = TempDir::new().unwrap();
+        let actual = State::load_state(tmpdir.path().join("state.json")).unwrap();
+        assert_eq!(State::default(), actual);
+    }
+
+    #[rstest]
+    fn test_state_dump_and_load_state(state: State) {
+        let tmpdir = TempDir::new().unwrap();
+        let path = tmpdir.path().join("nested/state.json");
+        state.dump_state(&path).unwrap();
+        let actual = State::load_state(&path).unwrap();
+        assert_eq!(state, actual);
+    }
+
+    #[rstest(profile, expect)]
+    #[case("foo", ProfileState { last_used: Some(Utc.with_ymd_and_hms(2022, 1, 2, 0, 0, 0).unwrap()), use_count: 4 })]
+    #[case("bar", ProfileState { last_used: Some(Utc.with_ymd_and_hms(2022, 1, 2, 0, 0, 0).unwrap()), use_count: 1 })]
+    fn test_state_record_usage(mut state: State, profile: &str, expect: ProfileState) {
+        state.record_usage(profile, Utc.with_ymd_and_hms(2022, 1, 2, 0, 0, 0).unwrap());
+        let actual = state.get_profile_state(profile).unwrap();
+        assert_eq!(&expect, actual);
+    }
+}
@@ -1,9 +1,81 @@
 use crate::ctx;
 
+use chrono::{DateTime, Duration, Utc};
+
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S UTC";
+/// Exit code when input is required in non-interactive mode, distinguished from other failures
+pub const INPUT_REQUIRED_EXIT_CODE: i32 = 3;
+
 pub fn fatal_ctxerr<T>(result: Result<T, ctx::CTXError>) -> T {
     match result {
         Ok(t) => t,
         Err(e) => match e {
+            ctx::CTXError::AuthFailed {
+                profile,
+                status,
+                attempts,
+                stderr,
+                log,
+                source,
+            } => {
+                match attempts {
+                    1 => error!(
+                        "<red>auth script of profile ({}) failed with {}</>",
+                        profile, status
+                    ),
+                    _ => error!(
+                        "<red>auth script of profile ({}) failed with {} after {} attempts</>",
+                        profile, status, attempts
+                    ),
+                }
+                if !stderr.is_empty() {
+                    error!("");
+                    error!("last lines of stderr:");
+                    for line in stderr {
+                        error!("  | {}", line);
+                    }
+                }
+                if let Some(log) = log {
+                    error!("");
+                    error!("see the full output in {}", log.display());
+                }
+                if let Some(source) = source {
+                    debug!("caused error: {:?}", source);
+                }
+                std::process::exit(1);
+            }
+            ctx::CTXError::AuthRequestFailed {
+                profile,
+                message,
+                source,
+            } => {
+                error!("<red>auth of profile ({}) failed: {}</>", profile, message);
+                if let Some(source) = source {
+                    debug!("caused error: {:?}", source);
+                }
+                std::process::exit(1);
+            }
+            ctx::CTXError::AuthTimeout {
+                profile,
+                timeout,
+                log,
+                source,
+            } => {
+                error!(
+                    "<red>auth script of profile ({}) timed out after {} seconds</>",
+                    profile,
+                    timeout.as_secs()
+                );
+                error!("");
+                error!("increase `timeout` of auth in your configurations (~/.awsctx/configs.yaml) if the script needs more time");
+                if let Some(log) = log {
+                    error!("see the output in {}", log.display());
+                }
+                if let Some(source) = source {
+                    debug!("caused error: {:?}", source);
+                }
+                std::process::exit(1);
+            }
             ctx::CTXError::CannotReadCredentials { source } => {
                 error!("<red>failed to read credentials, check your ~/.aws/credentials file</>");
                 if let Some(source) = source {
@@ -18,6 +90,15 @@ pub fn fatal_ctxerr<T>(result: Result<T, ctx::CTXError>) -> T {
                 }
                 std::process::exit(1);
             }
+            ctx::CTXError::ConfirmationRequired { message, source } => {
+                error!("<red>confirmation is required: {}</>", message);
+                error!("");
+                error!("run in a terminal or use `--yes` option to skip confirmation");
+                if let Some(source) = source {
+                    debug!("caused error: {:?}", source);
+                }
+                std::process::exit(1);
+            }
             ctx::CTXError::CredentialsIsBroken { source } => {
                 error!("<red>broken credentials, check your ~/.aws/credentials file</>");
                 if let Some(source) = source {
@@ -25,6 +106,54 @@ pub fn fatal_ctxerr<T>(result: Result<T, ctx::CTXError>) -> T {
                 }
                 std::process::exit(1);
             }
+            ctx::CTXError::InputRequired { message, source } => {
+                error!(
+                    "<red>input is required in non-interactive mode: {}</>",
+                    message
+                );
+                error!("");
+                error!("pass a profile name or a query matching a profile exactly, or use `--yes` option to skip confirmation");
+                if let Some(source) = source {
+                    debug!("caused error: {:?}", source);
+                }
+                std::process::exit(INPUT_REQUIRED_EXIT_CODE);
+            }
+            ctx::CTXError::InvalidArgument { message, source } => {
+                error!("<red>invalid argument: {}</>", message);
+                if let Some(source) = source {
+                    debug!("caused error: {:?}", source);
+                }
+                std::process::exit(1);
+            }
+            ctx::CTXError::HookFailed {
+                hook,
+                profile,
+                source,
+            } => {
+                error!(
+                    "<red>{} hook for profile ({}) failed, the operation is aborted</>",
+                    hook, profile
+                );
+                error!("");
+                error!("check `hooks` in your configurations (~/.awsctx/configs.yaml)");
+                if let Some(source) = source {
+                    debug!("caused error: {:?}", source);
+                }
+                std::process::exit(1);
+            }
+            ctx::CTXError::InvalidAlias {
+                alias: _,
+                message,
+                source,
+            } => {
+                error!("<red>invalid alias: {}</>", message);
+                error!("");
+                error!("modify ~/.awsctx/configs.yaml manually and try again");
+                if let Some(source) = source {
+                    debug!("caused error: {:?}", source);
+                }
+                std::process::exit(1);
+            }
             ctx::CTXError::InvalidConfigurations { message, source } => {
                 error!("<red>invalid configurations: {}</>", message);
                 error!("");
@@ -62,6 +191,26 @@ pub fn fatal_ctxerr<T>(result: Result<T, ctx::CTXError>) -> T {
                 }
                 std::process::exit(1);
             }
+            ctx::CTXError::NotConfirmed { profile, source } => {
+                error!(
+                    "<red>input does not match, operation on the protected profile ({}) is cancelled</>",
+                    profile
+                );
+                if let Some(source) = source {
+                    debug!("caused error: {:?}", source);
+                }
+                std::process::exit(1);
+            }
+            ctx::CTXError::NoSuchGroup { group, source } => {
+                error!(
+                    "<red>no such group: {}, check your ~/.awsctx/configs.yaml file</>",
+                    group
+                );
+                if let Some(source) = source {
+                    debug!("caused error: {:?}", source);
+                }
+                std::process::exit(1);
+            }
             ctx::CTXError::NoSuchProfile { profile, source } => {
                 error!(
                     "<red>no such profile: {}, check your ~/.aws/credentials file</>",
@@ -85,14 +234,152 @@ pub fn fatal_ctxerr<T>(result: Result<T, ctx::CTXError>) -> T {
 
 pub fn show_contexts(contexts: &[ctx::Context]) {
     for c in contexts.iter() {
-        if c.active {
-            info!("<green>* {}</>", c.name);
-        } else {
-            info!("  {}", c.name);
+        match (c.active, c.protected) {
+            (true, true) => info!("<green>*</> <red>{}</>", c.name),
+            (true, false) => info!("<green>* {}</>", c.name),
+            (false, true) => info!("  <red>{}</>", c.name),
+            (false, false) => info!("  {}", c.name),
+        }
+    }
+}
+
+/// Show a summary of auth for each profile
+pub fn show_auth_results(results: &[ctx::AuthResult]) {
+    let width = results
+        .iter()
+        .map(|r| r.profile.len())
+        .max()
+        .unwrap_or_default();
+    for r in results.iter() {
+        let duration = format!("{:.1}s", r.duration.as_secs_f64());
+        match &r.result {
+            Ok(_) => info!("<green>ok</>     {:width$} {:>6}", r.profile, duration),
+            Err(e) => error!(
+                "<red>failed</> {:width$} {:>6} {}",
+                r.profile,
+                duration,
+                error_summary(e)
+            ),
+        }
+    }
+}
+
+/// Summarize an error in a line without exiting
+fn error_summary(err: &ctx::CTXError) -> String {
+    match err {
+        ctx::CTXError::AuthRequestFailed { message, .. }
+        | ctx::CTXError::ConfirmationRequired { message, .. }
+        | ctx::CTXError::InputRequired { message, .. }
+        | ctx::CTXError::InvalidAlias { message, .. }
+        | ctx::CTXError::InvalidArgument { message, .. }
+        | ctx::CTXError::InvalidConfigurations { message, .. } => message.to_string(),
+        ctx::CTXError::AuthFailed { status, stderr, .. } => match stderr.last() {
+            Some(line) => format!("failed with {}, {}", status, line),
+            None => format!("failed with {}", status),
+        },
+        ctx::CTXError::HookFailed { hook, .. } => format!("{} hook failed", hook),
+        ctx::CTXError::AuthTimeout { timeout, .. } => {
+            format!("timed out after {} seconds", timeout.as_secs())
         }
+        _ => err.to_string(),
     }
 }
 
 pub fn show_context(contexts: &ctx::Context) {
     info!("{}", contexts.name)
 }
+
+pub fn show_context_details(details: &ctx::ContextDetails) {
+    match (details.active, details.protected) {
+        (true, true) => info!("<green>*</> <red>{}</>", details.name),
+        (true, false) => info!("<green>* {}</>", details.name),
+        (false, true) => info!("  <red>{}</>", details.name),
+        (false, false) => info!("  {}", details.name),
+    }
+    for line in format_context_details(details, Utc::now()) {
+        info!("    {}", line);
+    }
+}
+
+/// Format details of a context as lines, which are shared by `describe` and the preview of the finder
+pub fn format_context_details(details: &ctx::ContextDetails, now: DateTime<Utc>) -> Vec<String> {
+    let mut lines = vec![];
+    if details.protected {
+        lines.push("protected: yes".to_string());
+    }
+    if let Some(description) = &details.description {
+        lines.push(format!("description: {}", description));
+    }
+    if !details.aliases.is_empty() {
+        lines.push(format!("aliases: {}", details.aliases.join(", ")));
+    }
+    if !details.groups.is_empty() {
+        lines.push(format!("groups: {}", details.groups.join(", ")));
+    }
+    if !details.tags.is_empty() {
+        lines.push(format!("tags: {}", details.tags.join(", ")));
+    }
+    if let Some(region) = &details.region {
+        lines.push(format!("region: {}", region));
+    }
+    if let Some(access_key_id) = &details.access_key_id {
+        lines.push(format!("access key id: {}", mask(access_key_id, 4)));
+    }
+    if let Some(secret_access_key) = &details.secret_access_key {
+        lines.push(format!("secret access key: {}", mask(secret_access_key, 0)));
+    }
+    if let Some(key_type) = &details.key_type {
+        lines.push(format!("key type: {}", key_type));
+    }
+    if let Some(expiration) = details.expiration {
+        if expiration > now {
+            lines.push(format!(
+                "expiration: {} (expires in {})",
+                expiration.format(DATETIME_FORMAT),
+                format_duration(expiration - now)
+            ));
+        } else {
+            lines.push(format!(
+                "expiration: {} (expired {} ago)",
+                expiration.format(DATETIME_FORMAT),
+                format_duration(now - expiration)
+            ));
+        }
+    }
+    if let Some(last_used) = details.last_used {
+        lines.push(format!(
+            "last used: {} ({} ago)",
+            last_used.format(DATETIME_FORMAT),
+            format_duration(now - last_used)
+        ));
+    }
+    if let Some(auth_command) = &details.auth_command {
+        lines.push("auth command:".to_string());
+        lines.extend(auth_command.lines().map(|l| format!("  {}", l)));
+    }
+    lines
+}
+
+/// Mask a secret value except for the last 4 characters and the specified length of prefix
+fn mask(value: &str, prefix: usize) -> String {
+    let chars = value.chars().collect::<Vec<char>>();
+    if chars.len() <= prefix + 4 {
+        return "*".repeat(chars.len());
+    }
+    let suffix = chars.len() - 4;
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, c)| if i < prefix || i >= suffix { *c } else { '*' })
+        .collect()
+}
+
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    match minutes {
+        m if m < 1 => "less than a minute".to_string(),
+        m if m < 60 => format!("{}m", m),
+        m if m < 60 * 24 => format!("{}h {}m", m / 60, m % 60),
+        m => format!("{}d {}h", m / (60 * 24), m % (60 * 24) / 60),
+    }
+}
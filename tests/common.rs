@@ -1,13 +1,24 @@
+// helpers are shared by test crates each of which uses only some of them
+#![allow(dead_code)]
+
 use std::{
+    collections::HashMap,
     io::{Seek, SeekFrom, Write},
     rc::Rc,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
 };
 
 use maplit::hashmap;
 use rstest::*;
 use tempfile::NamedTempFile;
 
-use awsctx::{configs::Configs, creds::Credentials, ctx};
+use awsctx::{
+    configs::{Configs, GroupConfigs, ProfileConfigs},
+    creds::Credentials,
+    ctx,
+    prompt::Prompter,
+};
 
 #[fixture]
 pub fn aws_credentials_text() -> String {
@@ -74,14 +85,17 @@ pub fn contexts() -> Vec<ctx::Context> {
         ctx::Context {
             name: "bar".to_string(),
             active: false,
+            protected: false,
         },
         ctx::Context {
             name: "baz".to_string(),
             active: false,
+            protected: false,
         },
         ctx::Context {
             name: "foo".to_string(),
             active: true,
+            protected: false,
         },
     ]
 }
@@ -92,31 +106,257 @@ pub fn contexts_without_default() -> Vec<ctx::Context> {
         ctx::Context {
             name: "bar".to_string(),
             active: false,
+            protected: false,
         },
         ctx::Context {
             name: "foo".to_string(),
             active: false,
+            protected: false,
         },
     ]
 }
 
+pub fn auth_profile_configs(script: &str) -> ProfileConfigs {
+    ProfileConfigs {
+        auth: Some(script.to_string().into()),
+        ..Default::default()
+    }
+}
+
 #[fixture]
 pub fn configs() -> Rc<Configs> {
     Rc::new(Configs {
-        auth_commands: hashmap! {
-           "foo".to_string() => "echo auth".to_string(),
-              "bar".to_string() => "exit 1".to_string(),
-              Configs::DEFAULT_AUTH_COMMAND_KEY.to_string() => "echo default auth".to_string(),
+        profiles: hashmap! {
+            "foo".to_string() => auth_profile_configs("echo auth"),
+            "bar".to_string() => auth_profile_configs("exit 1"),
+            Configs::DEFAULT_AUTH_COMMAND_KEY.to_string() => auth_profile_configs("echo default auth"),
         },
+        ..Configs::empty()
     })
 }
 
 #[fixture]
 pub fn configs_without_default() -> Rc<Configs> {
     Rc::new(Configs {
-        auth_commands: hashmap! {
-           "foo".to_string() => "echo auth".to_string(),
-              "bar".to_string() => "exit 1".to_string(),
+        profiles: hashmap! {
+            "foo".to_string() => auth_profile_configs("echo auth"),
+            "bar".to_string() => auth_profile_configs("exit 1"),
+        },
+        ..Configs::empty()
+    })
+}
+
+pub fn alias_profile_configs(aliases: &[&str]) -> ProfileConfigs {
+    ProfileConfigs {
+        aliases: aliases.iter().map(|a| a.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+#[fixture]
+pub fn configs_with_aliases() -> Rc<Configs> {
+    Rc::new(Configs {
+        profiles: hashmap! {
+            "foo".to_string() => ProfileConfigs {
+                region: Some("ap-northeast-1".to_string()),
+                description: Some("foo account".to_string()),
+                tags: vec!["dev".to_string()],
+                env: hashmap! {"FOO".to_string() => "foo".to_string()},
+                ..alias_profile_configs(&["f", "dup"])
+            },
+            "bar".to_string() => alias_profile_configs(&["b", "dup"]),
+            // `baz` is defined in the credentials
+            "qux".to_string() => alias_profile_configs(&["baz"]),
+        },
+        ..Configs::empty()
+    })
+}
+
+#[fixture]
+pub fn configs_with_groups() -> Rc<Configs> {
+    Rc::new(Configs {
+        groups: hashmap! {
+            "dev".to_string() => GroupConfigs {
+                profiles: vec!["foo".to_string()],
+                ..Default::default()
+            },
+            "ba".to_string() => GroupConfigs {
+                pattern: Some("^ba".to_string()),
+                ..Default::default()
+            },
+        },
+        ..Configs::empty()
+    })
+}
+
+/// Prompter answering a pre-defined input, `None` behaves as if no terminal is available
+#[derive(Debug, Clone)]
+pub struct ScriptedPrompter {
+    pub input: Option<String>,
+}
+
+impl Prompter for ScriptedPrompter {
+    fn confirm(&self, message: &str, answers: &[String]) -> Result<bool, ctx::CTXError> {
+        match &self.input {
+            Some(input) => Ok(answers.contains(input)),
+            None => Err(ctx::CTXError::ConfirmationRequired {
+                message: message.to_string(),
+                source: None,
+            }),
+        }
+    }
+
+    fn input(&self, message: &str) -> Result<String, ctx::CTXError> {
+        self.input
+            .clone()
+            .ok_or_else(|| ctx::CTXError::InputRequired {
+                message: message.to_string(),
+                source: None,
+            })
+    }
+
+    fn clone_for_thread(&self) -> Box<dyn Prompter + Send> {
+        Box::new(self.clone())
+    }
+}
+
+#[fixture]
+pub fn configs_with_protected() -> Rc<Configs> {
+    Rc::new(Configs {
+        profiles: hashmap! {
+            "foo".to_string() => ProfileConfigs {
+                auth: Some("echo auth".to_string().into()),
+                protected: true,
+                ..Default::default()
+            },
+            "bar".to_string() => ProfileConfigs {
+                auth: Some("echo auth".to_string().into()),
+                account_id: Some("123456789012".to_string()),
+                protected: true,
+                ..Default::default()
+            },
         },
+        ..Configs::empty()
     })
 }
+
+/// Request received by [`MockServer`], whose header names are lowercase
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    /// Path and query of the request
+    pub url: String,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl MockRequest {
+    /// Parameters of the body encoded as a form
+    pub fn form(&self) -> HashMap<String, String> {
+        self.body
+            .split('&')
+            .filter_map(|p| p.split_once('='))
+            .map(|(k, v)| (percent_decode(k), percent_decode(v)))
+            .collect()
+    }
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                decoded.push(u8::from_str_radix(&value[i + 1..i + 3], 16).unwrap());
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            b => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).unwrap()
+}
+
+/// HTTP server mocking AWS APIs, which responds to requests by the handler and records them
+pub struct MockServer {
+    server: Arc<tiny_http::Server>,
+    thread: Option<JoinHandle<()>>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockServer {
+    /// Start a server on a free port, the handler returns a status code and a body
+    pub fn start(handler: impl Fn(&MockRequest) -> (u16, String) + Send + 'static) -> Self {
+        let server = Arc::new(tiny_http::Server::http("127.0.0.1:0").unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let thread = thread::spawn({
+            let (server, requests) = (server.clone(), requests.clone());
+            move || {
+                for mut request in server.incoming_requests() {
+                    let mut body = String::new();
+                    request.as_reader().read_to_string(&mut body).unwrap();
+                    let received = MockRequest {
+                        url: request.url().to_string(),
+                        headers: request
+                            .headers()
+                            .iter()
+                            .map(|h| (h.field.to_string().to_lowercase(), h.value.to_string()))
+                            .collect(),
+                        body,
+                    };
+                    let (status, body) = handler(&received);
+                    requests.lock().unwrap().push(received);
+                    request
+                        .respond(tiny_http::Response::from_string(body).with_status_code(status))
+                        .unwrap();
+                }
+            }
+        });
+        Self {
+            server,
+            thread: Some(thread),
+            requests,
+        }
+    }
+
+    pub fn endpoint(&self) -> String {
+        format!("http://{}", self.server.server_addr().to_ip().unwrap())
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Response of the action of STS with credentials of the access key id
+pub fn sts_response(action: &str, access_key_id: &str) -> String {
+    format!(
+        r#"<{action}Response xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <{action}Result>
+    <Credentials>
+      <AccessKeyId>{key}</AccessKeyId>
+      <SecretAccessKey>secret-{key}</SecretAccessKey>
+      <SessionToken>token-{key}</SessionToken>
+      <Expiration>2099-01-01T00:00:00Z</Expiration>
+    </Credentials>
+  </{action}Result>
+</{action}Response>"#,
+        action = action,
+        key = access_key_id
+    )
+}
@@ -0,0 +1,157 @@
+use std::rc::Rc;
+
+use awsctx::{
+    aws::AWS,
+    configs::{Configs, FinderAction},
+    ctx::{self, CTX},
+    interactive::{select_contexts_interactive, use_context_interactive, FinderOutcome},
+    selector::{NonInteractiveSelector, ScriptedSelector, Selection},
+};
+use rstest::*;
+use tempfile::NamedTempFile;
+
+mod common;
+use common::*;
+
+fn selection(name: &str, action: Option<FinderAction>) -> Selection {
+    Selection {
+        names: vec![name.to_string()],
+        action,
+    }
+}
+
+#[rstest(query, expect)]
+#[case("bar", "bar")]
+#[case("f", "foo")]
+fn test_use_context_interactive_with_exact_query(
+    configs_with_aliases: Rc<Configs>,
+    aws_credentials: NamedTempFile,
+    query: &str,
+    expect: &str,
+) {
+    let aws = AWS::new(configs_with_aliases, aws_credentials.path()).unwrap();
+    // the selector is never opened
+    let selector = ScriptedSelector::default();
+    let actual =
+        use_context_interactive(&aws, &selector, &ctx::ContextFilter::default(), Some(query))
+            .unwrap();
+    match actual {
+        FinderOutcome::Switched(actual) => assert_eq!(expect, actual.name),
+        _ => panic!("unexpected outcome: {:?}", actual),
+    }
+    assert_eq!(expect, aws.get_active_context().unwrap().name);
+}
+
+#[rstest(selections, expect, expect_active, expect_profiles)]
+#[case(
+    vec![selection("baz", None)],
+    FinderOutcome::Switched(ctx::Context { name: "baz".to_string(), active: true, protected: false }),
+    "baz",
+    vec!["bar", "baz", "foo"],
+)]
+#[case(
+    vec![selection("bar", Some(FinderAction::Auth))],
+    FinderOutcome::Authenticated(ctx::Context { name: "bar".to_string(), active: true, protected: false }),
+    "bar",
+    vec!["bar", "baz", "foo"],
+)]
+// the selector is opened again after describing
+#[case(
+    vec![selection("bar", Some(FinderAction::Describe)), selection("baz", Some(FinderAction::Copy))],
+    FinderOutcome::Copied(ctx::Context { name: "baz".to_string(), active: false, protected: false }),
+    "foo",
+    vec!["bar", "baz", "foo"],
+)]
+// the selector is opened again after deleting
+#[case(
+    vec![selection("bar", Some(FinderAction::Delete)), selection("baz", None)],
+    FinderOutcome::Switched(ctx::Context { name: "baz".to_string(), active: true, protected: false }),
+    "baz",
+    vec!["baz", "foo"],
+)]
+fn test_use_context_interactive_actions(
+    aws_credentials: NamedTempFile,
+    selections: Vec<Selection>,
+    expect: FinderOutcome,
+    expect_active: &str,
+    expect_profiles: Vec<&str>,
+) {
+    let configs = Rc::new(Configs {
+        profiles: maplit::hashmap! {
+            "bar".to_string() => auth_profile_configs("echo auth"),
+        },
+        ..Configs::empty()
+    });
+    let aws = AWS::new(configs, aws_credentials.path())
+        .unwrap()
+        .with_prompter(Box::new(ScriptedPrompter {
+            input: Some("bar".to_string()),
+        }));
+    let selector = ScriptedSelector::new(selections);
+    let actual =
+        use_context_interactive(&aws, &selector, &ctx::ContextFilter::default(), None).unwrap();
+    assert_eq!(expect, actual);
+    assert_eq!(expect_active, aws.get_active_context().unwrap().name);
+    let profiles = aws.list_contexts().unwrap();
+    let profiles = profiles
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<Vec<&str>>();
+    assert_eq!(expect_profiles, profiles);
+}
+
+#[rstest]
+fn test_use_context_interactive_cancelled(configs: Rc<Configs>, aws_credentials: NamedTempFile) {
+    let aws = AWS::new(configs, aws_credentials.path()).unwrap();
+    let selector = ScriptedSelector::default();
+    let actual = use_context_interactive(&aws, &selector, &ctx::ContextFilter::default(), None);
+    assert!(matches!(
+        actual,
+        Err(ctx::CTXError::NoContextIsSelected { source: _ })
+    ));
+    assert_eq!("foo", aws.get_active_context().unwrap().name);
+}
+
+#[rstest(query, expect_ok)]
+// an exact query never needs the finder
+#[case(Some("bar"), true)]
+#[case(Some("ba"), false)]
+#[case(None, false)]
+fn test_use_context_interactive_non_interactive(
+    configs: Rc<Configs>,
+    aws_credentials: NamedTempFile,
+    query: Option<&str>,
+    expect_ok: bool,
+) {
+    let aws = AWS::new(configs, aws_credentials.path()).unwrap();
+    let actual = use_context_interactive(
+        &aws,
+        &NonInteractiveSelector,
+        &ctx::ContextFilter::default(),
+        query,
+    );
+    match (expect_ok, actual) {
+        (true, Ok(FinderOutcome::Switched(actual))) => assert_eq!("bar", actual.name),
+        (false, Err(ctx::CTXError::InputRequired { .. })) => {
+            assert_eq!("foo", aws.get_active_context().unwrap().name)
+        }
+        (_, actual) => panic!("unexpected result: {:?}", actual),
+    }
+}
+
+#[rstest]
+fn test_select_contexts_interactive(configs: Rc<Configs>, aws_credentials: NamedTempFile) {
+    let aws = AWS::new(configs, aws_credentials.path()).unwrap();
+    let selector = ScriptedSelector::new(vec![Selection {
+        names: vec!["foo".to_string(), "bar".to_string()],
+        action: None,
+    }]);
+    let actual =
+        select_contexts_interactive(&aws, &selector, &ctx::ContextFilter::default(), true).unwrap();
+    let actual = actual
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<Vec<&str>>();
+    // in the order of the list, which starts with the active profile
+    assert_eq!(vec!["foo", "bar"], actual);
+}